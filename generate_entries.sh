@@ -1,3 +1,3 @@
 #!/bin/bash
 
-sea-orm-cli generate entity -o src/database/entity/generated --with-copy-enums --with-serde both
+sea-orm-cli generate entity -o src/database/entity/generated --with-copy-enums --with-serde both --date-time-crate time
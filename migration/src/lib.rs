@@ -1,12 +1,22 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20220101_000001_create_table;
+mod m20240301_000001_timestamp_types;
+mod m20240301_000002_access_entry_request_details;
+mod m20240301_000003_create_api_token;
+mod m20240301_000004_login_entry_request_id;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20220101_000001_create_table::Migration)]
+        vec![
+            Box::new(m20220101_000001_create_table::Migration),
+            Box::new(m20240301_000001_timestamp_types::Migration),
+            Box::new(m20240301_000002_access_entry_request_details::Migration),
+            Box::new(m20240301_000003_create_api_token::Migration),
+            Box::new(m20240301_000004_login_entry_request_id::Migration),
+        ]
     }
 }
@@ -0,0 +1,172 @@
+use sea_orm_migration::{prelude::*, sea_orm::DbBackend};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const LOGIN_ENTRY_INDEX: &str = "idx-login_entry-username-timestamp";
+const ACCESS_ENTRY_INDEX: &str = "idx-access_entry-username-timestamp";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        match manager.get_database_backend() {
+            // SQLite has no column types to speak of, but `CURRENT_TIMESTAMP` produces
+            // `YYYY-MM-DD HH:MM:SS` in UTC. Rewrite existing rows as RFC 3339, which is what the
+            // application writes, so that text comparisons order correctly.
+            DbBackend::Sqlite => {
+                for table in ["login_entry", "access_entry"] {
+                    manager
+                        .get_connection()
+                        .execute_unprepared(&format!(
+                            r#"UPDATE "{table}" SET "timestamp" = strftime('%Y-%m-%dT%H:%M:%SZ', "timestamp") WHERE "timestamp" NOT LIKE '%Z'"#
+                        ))
+                        .await?;
+                }
+            }
+            // Existing values are interpreted in the session time zone, which is the one
+            // `CURRENT_TIMESTAMP` used when they were inserted
+            DbBackend::Postgres => {
+                manager
+                    .alter_table(
+                        Table::alter()
+                            .table(LoginEntry::Table)
+                            .modify_column(&mut timestamp_column(LoginEntry::Timestamp, true))
+                            .to_owned(),
+                    )
+                    .await?;
+                manager
+                    .alter_table(
+                        Table::alter()
+                            .table(AccessEntry::Table)
+                            .modify_column(&mut timestamp_column(AccessEntry::Timestamp, true))
+                            .to_owned(),
+                    )
+                    .await?;
+            }
+            // MySQL's `TIMESTAMP` ends in 2038, so the columns stay `DATETIME`, which has no time
+            // zone. Convert existing values from the session time zone to UTC, which is what the
+            // application writes and assumes when reading.
+            DbBackend::MySql => {
+                for table in ["login_entry", "access_entry"] {
+                    manager
+                        .get_connection()
+                        .execute_unprepared(&format!(
+                            "UPDATE `{table}` SET `timestamp` = CONVERT_TZ(`timestamp`, @@session.time_zone, '+00:00')"
+                        ))
+                        .await?;
+                }
+            }
+        }
+
+        manager
+            .create_index(
+                Index::create()
+                    .name(LOGIN_ENTRY_INDEX)
+                    .table(LoginEntry::Table)
+                    .col(LoginEntry::Username)
+                    .col(LoginEntry::Timestamp)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name(ACCESS_ENTRY_INDEX)
+                    .table(AccessEntry::Table)
+                    .col(AccessEntry::Username)
+                    .col(AccessEntry::Timestamp)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name(LOGIN_ENTRY_INDEX)
+                    .table(LoginEntry::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name(ACCESS_ENTRY_INDEX)
+                    .table(AccessEntry::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        match manager.get_database_backend() {
+            DbBackend::Sqlite => {
+                for table in ["login_entry", "access_entry"] {
+                    manager
+                        .get_connection()
+                        .execute_unprepared(&format!(
+                            r#"UPDATE "{table}" SET "timestamp" = strftime('%Y-%m-%d %H:%M:%S', "timestamp")"#
+                        ))
+                        .await?;
+                }
+            }
+            DbBackend::Postgres => {
+                manager
+                    .alter_table(
+                        Table::alter()
+                            .table(LoginEntry::Table)
+                            .modify_column(&mut timestamp_column(LoginEntry::Timestamp, false))
+                            .to_owned(),
+                    )
+                    .await?;
+                manager
+                    .alter_table(
+                        Table::alter()
+                            .table(AccessEntry::Table)
+                            .modify_column(&mut timestamp_column(AccessEntry::Timestamp, false))
+                            .to_owned(),
+                    )
+                    .await?;
+            }
+            DbBackend::MySql => {
+                for table in ["login_entry", "access_entry"] {
+                    manager
+                        .get_connection()
+                        .execute_unprepared(&format!(
+                            "UPDATE `{table}` SET `timestamp` = CONVERT_TZ(`timestamp`, '+00:00', @@session.time_zone)"
+                        ))
+                        .await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn timestamp_column(column: impl IntoIden, with_time_zone: bool) -> ColumnDef {
+    let mut def = ColumnDef::new(column);
+    if with_time_zone {
+        def.timestamp_with_time_zone();
+    } else {
+        def.date_time();
+    }
+    def.not_null()
+        .default(SimpleExpr::Keyword(Keyword::CurrentTimestamp))
+        .to_owned()
+}
+
+#[derive(DeriveIden)]
+enum AccessEntry {
+    Table,
+    Timestamp,
+    Username,
+}
+
+#[derive(DeriveIden)]
+enum LoginEntry {
+    Table,
+    Timestamp,
+    Username,
+}
@@ -0,0 +1,60 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite only supports a single change per ALTER TABLE statement
+        for column in AccessEntry::details() {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(AccessEntry::Table)
+                        .add_column(ColumnDef::new(column).string().null())
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in AccessEntry::details() {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(AccessEntry::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden, Clone, Copy)]
+enum AccessEntry {
+    Table,
+    Host,
+    Uri,
+    Method,
+    ClientIp,
+    UserAgent,
+}
+
+impl AccessEntry {
+    fn details() -> [Self; 5] {
+        [
+            Self::Host,
+            Self::Uri,
+            Self::Method,
+            Self::ClientIp,
+            Self::UserAgent,
+        ]
+    }
+}
@@ -0,0 +1,82 @@
+use sea_orm_migration::{prelude::*, sea_orm::DbBackend};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let backend = manager.get_database_backend();
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApiToken::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ApiToken::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ApiToken::Username).string().not_null())
+                    .col(ColumnDef::new(ApiToken::Name).string().not_null())
+                    .col(
+                        ColumnDef::new(ApiToken::TokenHash)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(ApiToken::Scopes).string().not_null())
+                    .col(timestamp_column(backend, ApiToken::CreatedAt).not_null())
+                    .col(timestamp_column(backend, ApiToken::ExpiresAt).null())
+                    .col(timestamp_column(backend, ApiToken::LastUsedAt).null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-api_token-username")
+                    .table(ApiToken::Table)
+                    .col(ApiToken::Username)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ApiToken::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Tokens can expire far in the future, past the end of MySQL's `TIMESTAMP` in 2038. Its
+/// `DATETIME` has no time zone, values are stored in UTC.
+fn timestamp_column(backend: DbBackend, column: ApiToken) -> ColumnDef {
+    let mut def = ColumnDef::new(column);
+    match backend {
+        DbBackend::MySql => def.date_time(),
+        DbBackend::Postgres | DbBackend::Sqlite => def.timestamp_with_time_zone(),
+    };
+    def
+}
+
+#[derive(DeriveIden)]
+enum ApiToken {
+    Table,
+    Id,
+    Username,
+    Name,
+    TokenHash,
+    Scopes,
+    CreatedAt,
+    ExpiresAt,
+    LastUsedAt,
+}
@@ -1,7 +1,7 @@
 use proc_macro::TokenStream;
 use proc_macro2::Span;
 use quote::quote;
-use syn::{parse::Parse, parse_macro_input, ExprPath, Ident, LitStr, Token};
+use syn::{parse::Parse, parse_macro_input, DeriveInput, ExprPath, Ident, LitStr, Token};
 
 struct MacroInput {
     path: ExprPath,
@@ -43,3 +43,98 @@ pub fn export_seaorm(input_stream: TokenStream) -> TokenStream {
     }
     .into()
 }
+
+#[derive(Default)]
+struct LoginModuleAttributes {
+    name: Option<LitStr>,
+    subpath: Option<LitStr>,
+    display_name: Option<LitStr>,
+}
+
+impl LoginModuleAttributes {
+    fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        let mut attributes = Self::default();
+
+        for attr in input.attrs.iter().filter(|a| a.path().is_ident("login_module")) {
+            attr.parse_nested_meta(|meta| {
+                let target = if meta.path.is_ident("name") {
+                    &mut attributes.name
+                } else if meta.path.is_ident("subpath") {
+                    &mut attributes.subpath
+                } else if meta.path.is_ident("display_name") {
+                    &mut attributes.display_name
+                } else {
+                    return Err(meta.error("expected `name`, `subpath` or `display_name`"));
+                };
+
+                if target.is_some() {
+                    return Err(meta.error("duplicate attribute"));
+                }
+                *target = Some(meta.value()?.parse()?);
+                Ok(())
+            })?;
+        }
+
+        Ok(attributes)
+    }
+}
+
+/// Login module subpaths are mounted as actix scopes under `/login`, so they must start with a
+/// slash and must not end with one
+fn validate_subpath(subpath: &LitStr) -> syn::Result<()> {
+    let value = subpath.value();
+    if value.len() < 2 || !value.starts_with('/') || value.ends_with('/') {
+        return Err(syn::Error::new(
+            subpath.span(),
+            "subpath must start with '/' and must not end with '/'",
+        ));
+    }
+    if value.chars().any(char::is_whitespace) {
+        return Err(syn::Error::new(
+            subpath.span(),
+            "subpath must not contain whitespace",
+        ));
+    }
+
+    Ok(())
+}
+
+fn login_module_metadata(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let attributes = LoginModuleAttributes::parse(&input)?;
+    let ident = &input.ident;
+
+    let name = attributes.name.ok_or_else(|| {
+        syn::Error::new(
+            ident.span(),
+            "missing `#[login_module(name = \"...\")]` attribute",
+        )
+    })?;
+    let subpath = attributes.subpath.ok_or_else(|| {
+        syn::Error::new(
+            ident.span(),
+            "missing `#[login_module(subpath = \"...\")]` attribute",
+        )
+    })?;
+    validate_subpath(&subpath)?;
+    // Like the trait used to, fall back to the name if there's no dedicated display name
+    let display_name = attributes.display_name.unwrap_or_else(|| name.clone());
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics crate::controllers::LoginModuleMetadata for #ident #ty_generics #where_clause {
+            const NAME: &'static str = #name;
+            const SUBPATH: &'static str = #subpath;
+            const DISPLAY_NAME: &'static str = #display_name;
+        }
+    })
+}
+
+#[proc_macro_derive(LoginModuleMetadata, attributes(login_module))]
+pub fn derive_login_module_metadata(input_stream: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input_stream as DeriveInput);
+
+    login_module_metadata(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
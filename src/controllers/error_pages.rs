@@ -0,0 +1,139 @@
+use std::sync::Arc;
+
+use actix_session::SessionExt;
+use actix_web::{
+    dev::ServiceResponse,
+    http::{
+        header::{self, HeaderValue},
+        StatusCode,
+    },
+    middleware::ErrorHandlerResponse,
+    web, HttpRequest,
+};
+use tracing::{debug, error};
+use url::form_urlencoded;
+
+use crate::{
+    controllers::{accepts_html, request_id::RequestId},
+    errors::APIError,
+    settings::Settings,
+    templates::Templates,
+};
+
+/// Replaces error responses to browsers with an error page, leaving other clients the JSON
+/// response. Both show the request ID, which is also logged, so that reported problems can be
+/// traced.
+pub fn render<B>(res: ServiceResponse<B>) -> actix_web::Result<ErrorHandlerResponse<B>> {
+    let status = res.status();
+    let req = res.request();
+    let request_id = RequestId::of(req);
+
+    let error = res.response().error();
+    let message = error.map_or_else(|| status.to_string(), ToString::to_string);
+    if status.is_server_error() {
+        error!(
+            "{} {} failed with {status}: {message}",
+            req.method(),
+            req.path()
+        );
+    } else {
+        debug!(
+            "{} {} failed with {status}: {message}",
+            req.method(),
+            req.path()
+        );
+    }
+
+    let content_type = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .unwrap_or_default();
+    // Pages rendered by handlers, e.g. forms with validation errors, are left as they are
+    if content_type.starts_with("text/html") {
+        return Ok(ErrorHandlerResponse::Response(res.map_into_left_body()));
+    }
+    // Browsers also send `Authorization` while logging in with basic auth, so only `Accept` counts
+    if !accepts_html(req) {
+        // Only `AppError`s, other JSON responses such as failed readiness checks have their own body
+        if error.is_none() || !content_type.starts_with("application/json") {
+            return Ok(ErrorHandlerResponse::Response(res.map_into_left_body()));
+        }
+        let api_error = APIError::new(message, Some(request_id.to_string()));
+        let body = match serde_json::to_string(&api_error) {
+            Ok(body) => body,
+            Err(e) => {
+                error!("Error serializing error response: {:?}", e);
+                return Ok(ErrorHandlerResponse::Response(res.map_into_left_body()));
+            }
+        };
+        return Ok(replace_body(res, body));
+    }
+
+    let (Some(templates), Some(configuration)) = (
+        req.app_data::<web::Data<Templates>>(),
+        req.app_data::<web::Data<Arc<Settings>>>(),
+    ) else {
+        return Ok(ErrorHandlerResponse::Response(res.map_into_left_body()));
+    };
+
+    let base_path = configuration.http.base_path();
+    let language = templates.language(req, &req.get_session());
+    let mut ctx = templates.context(req, &language);
+    ctx.insert("base_path", base_path);
+    ctx.insert("status", &status.as_u16());
+    ctx.insert(
+        "message",
+        &templates.translate(&language, message_key(status)),
+    );
+    ctx.insert("error_id", request_id.as_str());
+    ctx.insert("retry_url", &retry_url(req, base_path));
+
+    let page = match templates.render("error.html", &ctx) {
+        Ok(page) => page,
+        Err(e) => {
+            error!("Error rendering template: {:?}", e);
+            return Ok(ErrorHandlerResponse::Response(res.map_into_left_body()));
+        }
+    };
+
+    let mut res = res;
+    res.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    Ok(replace_body(res, page))
+}
+
+/// Keeps the status and headers, e.g. `WWW-Authenticate`, but sends a different body
+fn replace_body<B>(res: ServiceResponse<B>, body: String) -> ErrorHandlerResponse<B> {
+    let (req, res) = res.into_parts();
+    let res = res.set_body(body).map_into_boxed_body();
+    ErrorHandlerResponse::Response(ServiceResponse::new(req, res).map_into_right_body())
+}
+
+/// Key of the translated explanation of an error status
+fn message_key(status: StatusCode) -> &'static str {
+    match status {
+        StatusCode::UNAUTHORIZED => "error_unauthorized",
+        StatusCode::FORBIDDEN => "error_forbidden",
+        StatusCode::NOT_FOUND => "error_not_found",
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT => {
+            "error_upstream"
+        }
+        status if status.is_server_error() => "error_internal",
+        _ => "error_bad_request",
+    }
+}
+
+/// The login page, sending the user on to the page they were trying to reach if known
+fn retry_url(req: &HttpRequest, base_path: &str) -> String {
+    let mut url = format!("{base_path}/login/");
+    if let Some((_, redirect)) =
+        form_urlencoded::parse(req.query_string().as_bytes()).find(|(key, _)| key == "redirect")
+    {
+        url.push_str("?redirect=");
+        url.extend(form_urlencoded::byte_serialize(redirect.as_bytes()));
+    }
+    url
+}
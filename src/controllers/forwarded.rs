@@ -0,0 +1,117 @@
+use actix_web::{http::header, HttpRequest};
+
+/// Details of the original request, as passed along by the reverse proxy in its authentication
+/// subrequest. Both the nginx (`X-Original-*`) and the Traefik/Caddy (`X-Forwarded-*`) header
+/// conventions are understood.
+#[derive(Debug, Default, Clone)]
+pub struct ForwardedRequest {
+    pub host: Option<String>,
+    pub uri: Option<String>,
+    pub method: Option<String>,
+    pub client_ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl ForwardedRequest {
+    pub fn from_request(req: &HttpRequest) -> Self {
+        Self {
+            // Proxy chains append to `X-Forwarded-Host`, the first entry is the client-facing one
+            host: first_header(req, &["X-Forwarded-Host", "Host"])
+                .and_then(|hosts| hosts.split(',').next().map(|host| host.trim().to_owned())),
+            uri: first_header(req, &["X-Original-URI", "X-Forwarded-Uri"]),
+            method: first_header(req, &["X-Original-Method", "X-Forwarded-Method"]),
+            client_ip: first_header(req, &["X-Real-IP"]).or_else(|| {
+                req.connection_info()
+                    .realip_remote_addr()
+                    .map(ToOwned::to_owned)
+            }),
+            user_agent: first_header(req, &[header::USER_AGENT.as_str()]),
+        }
+    }
+
+    /// Reconstructs the URL the client originally requested, if the proxy passed on the host
+    pub fn original_url(&self, scheme: &str) -> Option<String> {
+        let host = self.host.as_ref()?;
+        let uri = self.uri.as_deref().unwrap_or("/");
+        Some(format!("{scheme}://{host}{uri}"))
+    }
+}
+
+fn first_header(req: &HttpRequest, names: &[&str]) -> Option<String> {
+    names.iter().find_map(|name| {
+        req.headers()
+            .get(*name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(ToOwned::to_owned)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    #[test]
+    fn nginx_headers() {
+        let req = TestRequest::default()
+            .insert_header(("Host", "mordor:8080"))
+            .insert_header(("X-Forwarded-Host", "example.com"))
+            .insert_header(("X-Original-URI", "/courses/1"))
+            .insert_header(("X-Original-Method", "POST"))
+            .insert_header(("X-Real-IP", "192.0.2.1"))
+            .to_http_request();
+        let request = ForwardedRequest::from_request(&req);
+
+        assert_eq!(request.host.as_deref(), Some("example.com"));
+        assert_eq!(request.uri.as_deref(), Some("/courses/1"));
+        assert_eq!(request.method.as_deref(), Some("POST"));
+        assert_eq!(request.client_ip.as_deref(), Some("192.0.2.1"));
+        assert_eq!(
+            request.original_url("https").as_deref(),
+            Some("https://example.com/courses/1")
+        );
+    }
+
+    #[test]
+    fn traefik_headers() {
+        let req = TestRequest::default()
+            .insert_header(("X-Forwarded-Host", "example.com, proxy.internal"))
+            .insert_header(("X-Forwarded-Uri", "/a?b=c"))
+            .insert_header(("X-Forwarded-Method", "GET"))
+            .to_http_request();
+        let request = ForwardedRequest::from_request(&req);
+
+        // The first entry is the host the client asked for
+        assert_eq!(request.host.as_deref(), Some("example.com"));
+        assert_eq!(request.uri.as_deref(), Some("/a?b=c"));
+        assert_eq!(request.method.as_deref(), Some("GET"));
+    }
+
+    #[test]
+    fn falls_back_to_host_header() {
+        let req = TestRequest::default()
+            .insert_header(("Host", "mordor:8080"))
+            .insert_header(("X-Original-URI", " "))
+            .to_http_request();
+        let request = ForwardedRequest::from_request(&req);
+
+        assert_eq!(request.host.as_deref(), Some("mordor:8080"));
+        // Empty headers count as missing
+        assert_eq!(request.uri, None);
+        assert_eq!(
+            request.original_url("http").as_deref(),
+            Some("http://mordor:8080/")
+        );
+    }
+
+    #[test]
+    fn missing_host() {
+        let request = ForwardedRequest::from_request(&TestRequest::default().to_http_request());
+
+        assert_eq!(request.host, None);
+        assert_eq!(request.original_url("https"), None);
+    }
+}
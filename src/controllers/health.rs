@@ -0,0 +1,163 @@
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use actix_web::{get, web, HttpResponse, Responder};
+use migration::{Migrator, MigratorTrait};
+use sea_orm::DbConn;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::{
+    controllers::{
+        modules::saml_auth::SAMLLoginModule, request_id::RequestId, LoginModuleMetadata,
+    },
+    settings::Settings,
+    utils::get_http_client,
+};
+
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Ok,
+    Error,
+}
+
+#[derive(Debug, Serialize)]
+struct Check {
+    status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+impl Check {
+    fn ok() -> Self {
+        Self {
+            status: Status::Ok,
+            message: None,
+        }
+    }
+
+    fn error(message: impl ToString) -> Self {
+        Self {
+            status: Status::Error,
+            message: Some(message.to_string()),
+        }
+    }
+
+    fn is_ok(&self) -> bool {
+        matches!(self.status, Status::Ok)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ReadinessChecks {
+    database: Check,
+    migrations: Check,
+    /// Keyed by module name
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    saml_upstreams: BTreeMap<String, Check>,
+}
+
+#[derive(Debug, Serialize)]
+struct HealthResponse<T: Serialize> {
+    status: Status,
+    version: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    checks: Option<T>,
+}
+
+/// Liveness probe: if we can respond, the process is up
+#[get("/healthz/")]
+pub async fn healthz() -> impl Responder {
+    HttpResponse::Ok().json(HealthResponse::<()> {
+        status: Status::Ok,
+        version: crate::VERSION,
+        checks: None,
+    })
+}
+
+/// Readiness probe: checks that all dependencies required to serve requests are available
+#[get("/readyz/")]
+pub async fn readyz(
+    db_conn: web::Data<DbConn>,
+    configuration: web::Data<Arc<Settings>>,
+    request_id: RequestId,
+) -> impl Responder {
+    let database = match db_conn.ping().await {
+        Ok(_) => Check::ok(),
+        Err(e) => {
+            warn!("Readiness check failed, database unreachable: {e}");
+            Check::error(e)
+        }
+    };
+
+    let migrations = match Migrator::get_pending_migrations(db_conn.as_ref()).await {
+        Ok(pending) if pending.is_empty() => Check::ok(),
+        Ok(pending) => {
+            warn!(
+                "Readiness check failed, {} migration(s) pending",
+                pending.len()
+            );
+            Check::error(format!("{} migration(s) pending", pending.len()))
+        }
+        Err(e) => {
+            warn!("Readiness check failed, could not query migrations: {e}");
+            Check::error(e)
+        }
+    };
+
+    let mut saml_upstreams = BTreeMap::new();
+    for saml in configuration
+        .modules
+        .saml
+        .iter()
+        .filter(|saml| saml.common.enabled)
+    {
+        saml_upstreams.insert(
+            saml.common.name_or(SAMLLoginModule::NAME),
+            check_upstream(&saml.upstream_url, &request_id).await,
+        );
+    }
+
+    let checks = ReadinessChecks {
+        database,
+        migrations,
+        saml_upstreams,
+    };
+    let ready = checks.database.is_ok()
+        && checks.migrations.is_ok()
+        && checks.saml_upstreams.values().all(Check::is_ok);
+
+    let body = HealthResponse {
+        status: if ready { Status::Ok } else { Status::Error },
+        version: crate::VERSION,
+        checks: Some(checks),
+    };
+    if ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+/// Any HTTP response counts as reachable; we only care that the upstream is up
+async fn check_upstream(url: &url::Url, request_id: &RequestId) -> Check {
+    let client = match get_http_client(request_id) {
+        Ok(client) => client,
+        Err(e) => return Check::error(e),
+    };
+
+    match client
+        .get(url.clone())
+        .timeout(UPSTREAM_TIMEOUT)
+        .send()
+        .await
+    {
+        Ok(_) => Check::ok(),
+        Err(e) => {
+            warn!("Readiness check failed, upstream {url} unreachable: {e}");
+            Check::error(e)
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use actix_web::{http::header, web, HttpRequest, HttpResponse, Responder};
+
+/// Port of the HTTPS listener that plain HTTP requests are redirected to
+#[derive(Debug, Clone, Copy)]
+pub struct HttpsPort(pub u16);
+
+/// Permanently redirects any request to the same URL over HTTPS
+pub async fn redirect(req: HttpRequest, https_port: web::Data<HttpsPort>) -> impl Responder {
+    let connection_info = req.connection_info();
+    let host = connection_info.host();
+    // Strip the port of the plain HTTP listener, taking care not to break IPv6 literals
+    let hostname = match host.rsplit_once(':') {
+        Some((hostname, port)) if !port.contains(']') => hostname,
+        _ => host,
+    };
+
+    let location = match https_port.0 {
+        443 => format!("https://{hostname}{}", req.uri()),
+        port => format!("https://{hostname}:{port}{}", req.uri()),
+    };
+
+    HttpResponse::PermanentRedirect()
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
@@ -0,0 +1,9 @@
+use actix_web::{get, web, HttpResponse, Responder};
+
+use crate::jwt::JwtIssuer;
+
+/// Public keys to verify the JWTs returned by `/auth/` with
+#[get("/.well-known/jwks.json/")]
+pub async fn jwks(jwt_issuer: web::Data<JwtIssuer>) -> impl Responder {
+    HttpResponse::Ok().json(jwt_issuer.jwks())
+}
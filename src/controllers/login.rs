@@ -1,27 +1,41 @@
+use std::sync::Arc;
+
 use actix_session::Session;
 use actix_web::{
     get,
     http::{header::ContentType, StatusCode},
-    web, HttpResponse, Responder,
+    web, HttpRequest, HttpResponse, Responder,
 };
 use tera::Context;
 use tracing::error;
 
 use crate::{
-    controllers::{structures::QueryDataOptionalRedirect, TEMPLATES, USERNAME_SESSION_KEY},
+    controllers::{structures::QueryDataOptionalRedirect, USERNAME_SESSION_KEY},
     errors::AppError,
+    settings::Settings,
+    templates::Templates,
 };
 
 use super::AllModuleData;
 
 #[get("/")]
 pub async fn index(
+    req: HttpRequest,
     module_data: web::Data<AllModuleData>,
+    configuration: web::Data<Arc<Settings>>,
+    templates: web::Data<Templates>,
     query_data: web::Query<QueryDataOptionalRedirect>,
     session: Session,
 ) -> Result<impl Responder, AppError> {
-    let mut ctx = Context::new();
-    ctx.insert("modules", &module_data);
+    let language = templates.language(&req, &session);
+    let modules: AllModuleData = module_data
+        .iter()
+        .map(|module| module.localized(&language))
+        .collect();
+
+    let mut ctx = templates.context(&req, &language);
+    ctx.insert("modules", &modules);
+    ctx.insert("base_path", configuration.http.base_path());
 
     if let Some(target) = &query_data.redirect {
         // If the user is already logged in & we have a redirect target, redirect to the target
@@ -35,8 +49,33 @@ pub async fn index(
         ctx.insert("redirect", &target);
     }
 
-    match TEMPLATES.render("login.html", &ctx) {
-        Ok(s) => Ok(HttpResponse::build(StatusCode::OK)
+    render(&templates, "login.html", &ctx, StatusCode::OK)
+}
+
+/// Ends the session, whichever module it was started with
+#[get("/logout/")]
+pub async fn logout(
+    req: HttpRequest,
+    configuration: web::Data<Arc<Settings>>,
+    templates: web::Data<Templates>,
+    session: Session,
+) -> Result<HttpResponse, AppError> {
+    let language = templates.language(&req, &session);
+    session.purge();
+
+    let mut ctx = templates.context(&req, &language);
+    ctx.insert("base_path", configuration.http.base_path());
+    render(&templates, "logout.html", &ctx, StatusCode::OK)
+}
+
+fn render(
+    templates: &Templates,
+    name: &str,
+    ctx: &Context,
+    status: StatusCode,
+) -> Result<HttpResponse, AppError> {
+    match templates.render(name, ctx) {
+        Ok(s) => Ok(HttpResponse::build(status)
             .content_type(ContentType::html())
             .body(s)),
         Err(e) => {
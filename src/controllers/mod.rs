@@ -1,47 +1,49 @@
-use std::sync::Arc;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+};
 
+use actix_files::Files;
 use actix_session::Session;
 use actix_web::{
     get,
+    http::header,
     web::{self, ServiceConfig},
-    Responder,
+    HttpRequest, HttpResponse,
 };
-use lazy_static::lazy_static;
-use sea_orm::{DbConn, EntityTrait, Set};
+use color_eyre::{eyre::bail, Result};
+use sea_orm::DbConn;
 use serde::Serialize;
-use tera::Tera;
 use tracing::{debug, error};
+use url::form_urlencoded;
 
 use crate::{
-    database::entity::{AccessEntryActiveModel, AccessEntryEntity},
+    database::{
+        self,
+        access_recorder::{AccessRecord, AccessRecorder},
+        api_tokens,
+    },
     errors::{AppError, SessionError},
-    settings::Settings,
+    jwt::{JwtIssuer, API_TOKEN_MODULE},
+    settings::{http::AuthMode, modules::Common, Settings},
+    templates::Templates,
 };
 
+pub mod error_pages;
+mod forwarded;
+mod health;
+pub mod https_redirect;
+mod jwks;
 mod login;
 pub mod modules;
+pub mod request_id;
 mod structures;
+mod tokens;
 
-pub const USERNAME_SESSION_KEY: &str = "username";
+pub use forwarded::ForwardedRequest;
 
-lazy_static! {
-    pub static ref TEMPLATES: Tera = {
-        let mut tera = Tera::default();
-        match tera.add_raw_templates(vec![(
-            "login.html",
-            include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/src/_static/login.html"
-            )),
-        )]) {
-            Ok(_) => tera,
-            Err(e) => {
-                error!("Error parsing templates: {e:?}");
-                panic!("Error parsing templates: {e:?}");
-            }
-        }
-    };
-}
+pub const USERNAME_SESSION_KEY: &str = "username";
+pub const MODULE_SESSION_KEY: &str = "module";
 
 pub struct ModuleBuilder {
     modules: Vec<Box<dyn LoginModule>>,
@@ -56,92 +58,323 @@ impl ModuleBuilder {
         self.modules.push(module);
     }
 
-    pub fn build(self) -> Vec<Box<dyn LoginModule>> {
-        self.modules
+    /// Validates the registered modules and returns them in registration order
+    pub fn build(self) -> Result<Vec<Box<dyn LoginModule>>> {
+        if self.modules.is_empty() {
+            bail!("At least one login module must be enabled");
+        }
+
+        let mut names = HashSet::new();
+        let mut subpaths = HashSet::new();
+        for module in &self.modules {
+            let name = module.name();
+            if name.is_empty() {
+                bail!("Login module names must not be empty");
+            }
+            if !names.insert(name) {
+                bail!("Name '{name}' is used by more than one login module");
+            }
+
+            // Defaults are validated when deriving `LoginModuleMetadata`, configured values here
+            let subpath = module.subpath();
+            if subpath.len() < 2 || !subpath.starts_with('/') || subpath.ends_with('/') {
+                bail!(
+                    "Subpath '{subpath}' of login module '{}' must start with '/' and must not end with '/'",
+                    module.name()
+                );
+            }
+            if subpath.chars().any(char::is_whitespace) {
+                bail!(
+                    "Subpath '{subpath}' of login module '{}' must not contain whitespace",
+                    module.name()
+                );
+            }
+            if !subpaths.insert(subpath) {
+                bail!("Subpath '{subpath}' is used by more than one login module");
+            }
+        }
+
+        Ok(self.modules)
     }
 }
 
 pub type AllModuleData = Vec<ModuleData>;
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ModuleData {
-    pub name: &'static str,
-    pub subpath: &'static str,
-    pub display_name: &'static str,
+    pub name: String,
+    pub subpath: String,
+    pub display_name: String,
+    pub display_names: BTreeMap<String, String>,
 }
 
-pub trait LoginModule {
-    fn name(&self) -> &'static str;
-    fn subpath(&self) -> &'static str;
-    fn display_name(&self) -> &'static str {
-        self.name()
+impl ModuleData {
+    /// The module with its display name in the given language, if it has been translated
+    pub fn localized(&self, language: &str) -> Self {
+        let mut module = self.clone();
+        if let Some(display_name) = self.display_names.get(language) {
+            module.display_name = display_name.clone();
+        }
+        module
+    }
+}
+
+/// Defaults of a login module type, used when an instance doesn't override them in the
+/// configuration. Usually derived with `#[derive(LoginModuleMetadata)]`, which validates the
+/// subpath at compile time.
+pub trait LoginModuleMetadata {
+    const NAME: &'static str;
+    const SUBPATH: &'static str;
+    const DISPLAY_NAME: &'static str;
+}
+
+/// Settings every login module instance has, with the module type's defaults filled in
+#[derive(Debug, Clone)]
+pub struct ModuleInfo {
+    pub name: String,
+    pub subpath: String,
+    pub display_name: String,
+    /// Translations of the display name by language code
+    pub display_names: BTreeMap<String, String>,
+    /// Groups of users logged in through this module
+    pub groups: Vec<String>,
+}
+
+impl ModuleInfo {
+    pub fn new<M: LoginModuleMetadata>(common: &Common) -> Self {
+        Self {
+            name: common.name_or(M::NAME),
+            subpath: common
+                .subpath
+                .clone()
+                .unwrap_or_else(|| M::SUBPATH.to_owned()),
+            display_name: common
+                .display_name
+                .clone()
+                .unwrap_or_else(|| M::DISPLAY_NAME.to_owned()),
+            display_names: common.display_names.clone(),
+            groups: common.groups.clone(),
+        }
     }
+}
+
+pub trait LoginModule: Send + Sync {
+    fn info(&self) -> &ModuleInfo;
     fn register_controller(&self, svc_cfg: &mut ServiceConfig);
+
+    fn name(&self) -> &str {
+        &self.info().name
+    }
+    fn subpath(&self) -> &str {
+        &self.info().subpath
+    }
+    fn display_name(&self) -> &str {
+        &self.info().display_name
+    }
+    fn display_names(&self) -> &BTreeMap<String, String> {
+        &self.info().display_names
+    }
+    fn groups(&self) -> &[String] {
+        &self.info().groups
+    }
+}
+
+/// Name of the login module instance handling a request, available to the module's handlers
+#[derive(Debug, Clone)]
+pub struct ModuleName(pub String);
+
+/// Groups of each login module instance, by name
+type ModuleGroups = HashMap<String, Vec<String>>;
+
+/// Stores the logged in user in the session, along with the module they logged in through
+pub fn log_in(session: &Session, user_id: &str, module: &ModuleName) -> Result<(), SessionError> {
+    session.insert(USERNAME_SESSION_KEY, user_id)?;
+    session.insert(MODULE_SESSION_KEY, &module.0)?;
+    Ok(())
 }
 
 pub fn initialize(
     svc_cfg: &mut ServiceConfig,
-    modules: impl IntoIterator<Item = Box<dyn LoginModule>>,
+    configuration: &Settings,
+    modules: &[Box<dyn LoginModule>],
+    templates: &Templates,
 ) {
     // Initialize login modules
     let mut login_scope = web::scope("/login").service(login::index);
     let mut data: AllModuleData = vec![];
+    let mut groups = ModuleGroups::new();
     for module in modules {
         debug!("Registering login module: {}", module.name());
 
-        let subpath = module.subpath();
-        assert!(
-            subpath.starts_with('/') && !subpath.ends_with('/'),
-            "Subpath must start with '/' and must not end with '/'"
-        );
-
-        let module_scope =
-            web::scope(module.subpath()).configure(|sc| module.register_controller(sc));
+        let module_scope = web::scope(module.subpath())
+            .app_data(web::Data::new(ModuleName(module.name().to_owned())))
+            .configure(|sc| module.register_controller(sc));
 
         login_scope = login_scope.service(module_scope);
 
         data.push(ModuleData {
-            name: module.name(),
-            subpath: &module.subpath()[1..], // Remove leading slash, as it's a relative path, not an absolute one!
-            display_name: module.display_name(),
+            name: module.name().to_owned(),
+            subpath: module.subpath()[1..].to_owned(), // Remove leading slash, as it's a relative path, not an absolute one!
+            display_name: module.display_name().to_owned(),
+            display_names: module.display_names().clone(),
         });
+        groups.insert(module.name().to_owned(), module.groups().to_vec());
     }
     login_scope = login_scope
         .app_data(web::Data::new(data))
         .service(login::index);
 
     // Initialize auth scope
-    let auth_scope = web::scope("/auth").service(auth);
+    let auth_scope = web::scope("/auth")
+        .app_data(web::Data::new(groups))
+        .service(auth);
+
+    let mut base_scope = web::scope(configuration.http.base_path())
+        .service(login_scope)
+        .service(auth_scope)
+        .service(login::logout)
+        .service(health::healthz)
+        .service(health::readyz);
+
+    if configuration.jwt.is_some() {
+        base_scope = base_scope.service(jwks::jwks);
+    }
 
-    svc_cfg.service(login_scope).service(auth_scope);
+    let api_tokens = &configuration.api_tokens;
+    if api_tokens.enabled && api_tokens.self_service {
+        base_scope = base_scope.service(
+            web::scope("/tokens")
+                .service(tokens::index)
+                .service(tokens::create)
+                .service(tokens::revoke),
+        );
+    }
+
+    if let Some(static_dir) = templates.static_dir() {
+        base_scope = base_scope.service(Files::new("/static", static_dir));
+    }
+
+    svc_cfg.service(base_scope);
 }
 
 #[get("/")]
 async fn auth(
+    req: HttpRequest,
     session: Session,
     db_conn: web::Data<DbConn>,
     configuration: web::Data<Arc<Settings>>,
-) -> Result<impl Responder, AppError> {
-    let user_id: String = session
-        .get(USERNAME_SESSION_KEY)
-        .map_err(Into::<SessionError>::into)?
-        .ok_or(AppError::NotAuthenticated)?;
-
-    if configuration.store_access_entries {
-        let access_entry = AccessEntryActiveModel {
-            username: Set(user_id.clone()),
-            ..Default::default()
-        };
-        if let Err(e) = AccessEntryEntity::insert(access_entry)
-            .exec(db_conn.as_ref())
-            .await
+    module_groups: web::Data<ModuleGroups>,
+    access_recorder: Option<web::Data<AccessRecorder>>,
+    jwt_issuer: Option<web::Data<JwtIssuer>>,
+) -> Result<HttpResponse, AppError> {
+    let request = ForwardedRequest::from_request(&req);
+
+    let (user_id, module) = match identify(&req, &session, &db_conn, &configuration, &request).await
+    {
+        Ok(identity) => identity,
+        Err(AppError::NotAuthenticated)
+            if configuration.http.auth_mode == AuthMode::ForwardAuth && is_browser(&req) =>
         {
-            error!(
-                "Error inserting access entry (user_id='{user_id}'): {e}",
-                user_id = user_id,
-                e = e
-            );
+            return Ok(login_redirect(&req, &configuration, &request));
         }
+        Err(e) => return Err(e),
     };
 
-    Ok(format!("Logged in as '{user_id}'"))
+    // Only registered when `store_access_entries` is enabled
+    if let Some(access_recorder) = access_recorder {
+        access_recorder.record(AccessRecord {
+            username: user_id.clone(),
+            timestamp: database::now(),
+            request,
+        });
+    }
+
+    let mut response = HttpResponse::Ok();
+    // Only registered when `jwt` is configured
+    if let Some(jwt_issuer) = jwt_issuer {
+        let groups = module
+            .as_ref()
+            .and_then(|module| module_groups.get(module))
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        response.insert_header((
+            jwt_issuer.header(),
+            jwt_issuer.issue(&user_id, module.as_deref(), groups),
+        ));
+    }
+
+    Ok(response.body(format!("Logged in as '{user_id}'")))
+}
+
+/// Returns the user making the request and the login module they used, from either an API token
+/// or the session
+async fn identify(
+    req: &HttpRequest,
+    session: &Session,
+    db_conn: &DbConn,
+    configuration: &Settings,
+    request: &ForwardedRequest,
+) -> Result<(String, Option<String>), AppError> {
+    match bearer_token(req) {
+        Some(token) if configuration.api_tokens.enabled => {
+            let model = api_tokens::authenticate(db_conn, token, request)
+                .await
+                .map_err(|e| {
+                    error!("Error checking API token: {e}");
+                    AppError::Internal
+                })?
+                .ok_or(AppError::NotAuthenticated)?;
+            Ok((model.username, Some(API_TOKEN_MODULE.to_owned())))
+        }
+        _ => Ok((
+            session
+                .get(USERNAME_SESSION_KEY)
+                .map_err(Into::<SessionError>::into)?
+                .ok_or(AppError::NotAuthenticated)?,
+            session
+                .get(MODULE_SESSION_KEY)
+                .map_err(Into::<SessionError>::into)?,
+        )),
+    }
+}
+
+/// Whether the client asked for HTML, as browsers do when navigating
+fn accepts_html(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"))
+}
+
+/// Browsers can follow a redirect to the login page, other clients can't log in there anyway
+fn is_browser(req: &HttpRequest) -> bool {
+    accepts_html(req) && !req.headers().contains_key(header::AUTHORIZATION)
+}
+
+/// Redirects to the login page, which sends the user back to the original URL afterwards
+fn login_redirect(
+    req: &HttpRequest,
+    configuration: &Settings,
+    request: &ForwardedRequest,
+) -> HttpResponse {
+    let mut location = configuration.http.login_url();
+    if let Some(original_url) = request.original_url(req.connection_info().scheme()) {
+        location.push_str("?redirect=");
+        location.extend(form_urlencoded::byte_serialize(original_url.as_bytes()));
+    }
+
+    HttpResponse::Found()
+        .append_header((header::LOCATION, location))
+        .finish()
+}
+
+/// Returns mordor's API token from the `Authorization` header. Bearer tokens without our prefix
+/// belong to the protected application and are ignored.
+fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|token| token.starts_with(api_tokens::TOKEN_PREFIX))
 }
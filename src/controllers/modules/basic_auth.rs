@@ -6,27 +6,42 @@ use actix_web::{
     Error, HttpResponse, Responder,
 };
 use actix_web_httpauth::{extractors::basic::BasicAuth, middleware::HttpAuthentication};
-use sea_orm::{ColumnTrait, DbConn, EntityTrait, QueryFilter, Set};
+use mordor_macros::LoginModuleMetadata;
+use sea_orm::{ColumnTrait, DbConn, EntityTrait, QueryFilter};
 use tracing::{error, warn};
 
+use super::store_login_attempt;
 use crate::{
-    controllers::{structures::QueryDataOptionalRedirect, LoginModule, USERNAME_SESSION_KEY},
-    database::entity::{
-        BasicLoginUserColumn, BasicLoginUserEntity, LoginEntryActiveModel, LoginEntryEntity,
+    controllers::{
+        log_in, request_id::RequestId, structures::QueryDataOptionalRedirect, LoginModule,
+        ModuleInfo, ModuleName,
     },
-    errors::{AppError, SessionError},
+    database::entity::{BasicLoginUserColumn, BasicLoginUserEntity},
+    errors::AppError,
+    settings::modules::basic,
 };
 
-#[derive(Debug, Default)]
-pub struct BasicAuthLoginModule;
+#[derive(Debug, LoginModuleMetadata)]
+#[login_module(
+    name = "BasicAuth",
+    subpath = "/basic",
+    display_name = "External users"
+)]
+pub struct BasicAuthLoginModule {
+    info: ModuleInfo,
+}
 
-impl LoginModule for BasicAuthLoginModule {
-    fn name(&self) -> &'static str {
-        "BasicAuth"
+impl BasicAuthLoginModule {
+    pub fn new(settings: &basic::Settings) -> Self {
+        Self {
+            info: ModuleInfo::new::<Self>(&settings.common),
+        }
     }
+}
 
-    fn subpath(&self) -> &'static str {
-        "/basic"
+impl LoginModule for BasicAuthLoginModule {
+    fn info(&self) -> &ModuleInfo {
+        &self.info
     }
 
     fn register_controller(&self, svc_cfg: &mut ServiceConfig) {
@@ -36,37 +51,6 @@ impl LoginModule for BasicAuthLoginModule {
                 .service(index),
         );
     }
-
-    fn display_name(&self) -> &'static str {
-        "External users"
-    }
-}
-
-async fn store_login_attempt(
-    user_id: &str,
-    success: bool,
-    remote_addr: String,
-    db_conn: &DbConn,
-) -> bool {
-    let login_entry = LoginEntryActiveModel {
-        username: Set(user_id.to_owned()),
-        success: Set(success),
-        ip_address: Set(remote_addr),
-        ..Default::default()
-    };
-
-    match LoginEntryEntity::insert(login_entry).exec(db_conn).await {
-        Ok(_) => true,
-        Err(e) => {
-            error!(
-                "Error inserting login entry (user_id='{user_id}', success={success}): {e}",
-                user_id = user_id,
-                success = success,
-                e = e
-            );
-            false
-        }
-    }
 }
 
 async fn validator(
@@ -90,6 +74,8 @@ async fn validator(
         }
     };
 
+    let request_id = RequestId::of(&req);
+
     // Extract the username
     let user_id = credentials.user_id();
     // Get the remote address
@@ -109,7 +95,7 @@ async fn validator(
         Ok(None) => {
             // If the username is not found, store a failed login attempt and return an error
             warn!("User '{}' not found", user_id);
-            store_login_attempt(user_id, false, remote_addr, &db_conn).await;
+            store_login_attempt(user_id, false, remote_addr, &request_id, &db_conn).await;
             return Err((AppError::NotAuthenticated.into(), req));
         }
         Err(e) => {
@@ -125,7 +111,7 @@ async fn validator(
         Ok(false) => {
             // If the password does not match, store a failed login attempt and return an error
             error!("Password does not match");
-            store_login_attempt(user_id, false, remote_addr, &db_conn).await;
+            store_login_attempt(user_id, false, remote_addr, &request_id, &db_conn).await;
             return Err((AppError::NotAuthenticated.into(), req));
         }
         Err(e) => {
@@ -136,20 +122,19 @@ async fn validator(
     };
 
     // Store a successful login attempt
-    store_login_attempt(user_id, true, remote_addr, &db_conn).await;
+    store_login_attempt(user_id, true, remote_addr, &request_id, &db_conn).await;
     Ok(req)
 }
 
 #[get("/")]
 async fn index(
     session: Session,
+    module: web::Data<ModuleName>,
     auth_data: BasicAuth,
     query_data: web::Query<QueryDataOptionalRedirect>,
 ) -> Result<impl Responder, AppError> {
     let user_id = auth_data.user_id().to_string();
-    session
-        .insert(USERNAME_SESSION_KEY, &user_id)
-        .map_err(Into::<SessionError>::into)?;
+    log_in(&session, &user_id, &module).map_err(AppError::from)?;
 
     if let Some(target) = &query_data.redirect {
         Ok(HttpResponse::TemporaryRedirect()
@@ -0,0 +1,204 @@
+use std::sync::Arc;
+
+use actix_session::Session;
+use actix_web::{
+    get,
+    web::{self, ServiceConfig},
+    HttpRequest, HttpResponse, Responder,
+};
+use color_eyre::{eyre::bail, Result};
+use mordor_macros::LoginModuleMetadata;
+use percent_encoding::percent_decode_str;
+use sea_orm::DbConn;
+use tracing::{debug, warn};
+use x509_parser::{certificate::X509Certificate, extensions::GeneralName, pem, prelude::FromDer};
+
+use super::store_login_attempt;
+use crate::{
+    controllers::{
+        log_in, request_id::RequestId, structures::QueryDataOptionalRedirect, LoginModule,
+        ModuleInfo, ModuleName,
+    },
+    errors::AppError,
+    settings::modules::client_cert::{self, CertificateField, Rule},
+    tls::PeerCertificate,
+};
+
+/// Value of `verify_header` for certificates the proxy verified successfully
+const VERIFY_SUCCESS: &str = "SUCCESS";
+
+#[derive(Debug, LoginModuleMetadata)]
+#[login_module(
+    name = "ClientCert",
+    subpath = "/cert",
+    display_name = "Client certificate"
+)]
+pub struct ClientCertLoginModule {
+    config: Arc<client_cert::Settings>,
+    info: ModuleInfo,
+}
+
+impl ClientCertLoginModule {
+    pub fn new(settings: &client_cert::Settings) -> Result<Self> {
+        let info = ModuleInfo::new::<Self>(&settings.common);
+        let name = &info.name;
+        if settings.rules.is_empty() {
+            bail!("Login module '{name}' needs at least one rule");
+        }
+        if settings.verify_header.is_some() && settings.header.is_none() {
+            bail!("Login module '{name}' has a `verify_header` but no `header`");
+        }
+
+        Ok(Self {
+            config: Arc::new(settings.clone()),
+            info,
+        })
+    }
+}
+
+impl LoginModule for ClientCertLoginModule {
+    fn info(&self) -> &ModuleInfo {
+        &self.info
+    }
+
+    fn register_controller(&self, svc_cfg: &mut ServiceConfig) {
+        svc_cfg
+            .service(index)
+            .app_data(web::Data::from(self.config.clone()));
+    }
+}
+
+#[get("/")]
+async fn index(
+    req: HttpRequest,
+    config: web::Data<client_cert::Settings>,
+    db_conn: web::Data<DbConn>,
+    session: Session,
+    module: web::Data<ModuleName>,
+    query_data: web::Query<QueryDataOptionalRedirect>,
+    request_id: RequestId,
+) -> Result<impl Responder, AppError> {
+    let remote_addr = req
+        .connection_info()
+        .realip_remote_addr()
+        .unwrap_or("UNAVAILABLE")
+        .to_owned();
+
+    let Some(der) = presented_certificate(&req, &config) else {
+        debug!("No client certificate presented");
+        return Err(AppError::NotAuthenticated);
+    };
+
+    let certificate = match X509Certificate::from_der(&der) {
+        Ok((_, certificate)) => certificate,
+        Err(e) => {
+            warn!("Error parsing client certificate: {e}");
+            return Err(AppError::NotAuthenticated);
+        }
+    };
+
+    let Some(user_id) = map_username(&certificate, &config.rules) else {
+        warn!(
+            "No rule matches client certificate (subject='{}')",
+            certificate.subject()
+        );
+        return Err(AppError::NotAuthenticated);
+    };
+
+    store_login_attempt(&user_id, true, remote_addr, &request_id, &db_conn).await;
+    log_in(&session, &user_id, &module).map_err(AppError::from)?;
+
+    if let Some(target) = &query_data.redirect {
+        Ok(HttpResponse::TemporaryRedirect()
+            .append_header(("Location", target.clone()))
+            .finish())
+    } else {
+        Ok(HttpResponse::Ok().body(format!("Logged in as '{user_id}'")))
+    }
+}
+
+/// Returns the DER encoded client certificate, either from mordor's own TLS handshake or, if the
+/// request comes from a trusted proxy, from the configured header
+fn presented_certificate(req: &HttpRequest, config: &client_cert::Settings) -> Option<Vec<u8>> {
+    if let Some(PeerCertificate(der)) = req.conn_data::<PeerCertificate>() {
+        return Some(der.clone());
+    }
+
+    let header = config.header.as_ref()?;
+    // Requests over Unix domain sockets have no peer address, and come from a local proxy
+    if let Some(peer_addr) = req.peer_addr() {
+        if !config.trusted_proxies.contains(&peer_addr.ip()) {
+            debug!("Ignoring client certificate header from untrusted peer {peer_addr}");
+            return None;
+        }
+    }
+
+    if let Some(verify_header) = &config.verify_header {
+        let verified = req
+            .headers()
+            .get(verify_header)
+            .and_then(|value| value.to_str().ok());
+        if verified != Some(VERIFY_SUCCESS) {
+            warn!("Proxy did not verify the client certificate: {verified:?}");
+            return None;
+        }
+    }
+
+    let value = req.headers().get(header)?.to_str().ok()?;
+    let pem = percent_decode_str(value).decode_utf8().ok()?;
+    match pem::parse_x509_pem(pem.as_bytes()) {
+        Ok((_, pem)) => Some(pem.contents),
+        Err(e) => {
+            warn!("Error parsing forwarded client certificate: {e}");
+            None
+        }
+    }
+}
+
+/// Applies the rules in order, returning the username produced by the first match
+fn map_username(certificate: &X509Certificate, rules: &[Rule]) -> Option<String> {
+    let alternative_names = match certificate.subject_alternative_name() {
+        Ok(Some(extension)) => extension.value.general_names.clone(),
+        Ok(None) => vec![],
+        Err(e) => {
+            warn!("Error parsing subject alternative names: {e}");
+            vec![]
+        }
+    };
+
+    for rule in rules {
+        let values: Vec<&str> = match rule.field {
+            CertificateField::CommonName => certificate
+                .subject()
+                .iter_common_name()
+                .filter_map(|cn| cn.as_str().ok())
+                .collect(),
+            CertificateField::Email => alternative_names
+                .iter()
+                .filter_map(|name| match name {
+                    GeneralName::RFC822Name(email) => Some(*email),
+                    _ => None,
+                })
+                .collect(),
+            CertificateField::Dns => alternative_names
+                .iter()
+                .filter_map(|name| match name {
+                    GeneralName::DNSName(dns) => Some(*dns),
+                    _ => None,
+                })
+                .collect(),
+        };
+
+        for value in values {
+            if let Some(captures) = rule.pattern.captures(value) {
+                let mut username = String::new();
+                captures.expand(&rule.username, &mut username);
+                if !username.is_empty() {
+                    return Some(username);
+                }
+            }
+        }
+    }
+
+    None
+}
@@ -1,2 +1,86 @@
+use color_eyre::Result;
+use sea_orm::{DbConn, EntityTrait, Set};
+use tracing::error;
+
+use crate::{
+    controllers::{request_id::RequestId, LoginModule, ModuleBuilder},
+    database::{
+        self,
+        entity::{LoginEntryActiveModel, LoginEntryEntity},
+    },
+    settings::modules::Settings,
+};
+
+use self::{
+    basic_auth::BasicAuthLoginModule, client_cert_auth::ClientCertLoginModule,
+    saml_auth::SAMLLoginModule,
+};
+
 pub mod basic_auth;
+pub mod client_cert_auth;
 pub mod saml_auth;
+
+/// Instantiates the login modules enabled in the configuration, ordered as configured
+pub fn from_settings(settings: &Settings) -> Result<Vec<Box<dyn LoginModule>>> {
+    let mut modules: Vec<(i32, Box<dyn LoginModule>)> = vec![];
+
+    for basic in settings.basic.iter().filter(|basic| basic.common.enabled) {
+        modules.push((
+            basic.common.order,
+            Box::new(BasicAuthLoginModule::new(basic)),
+        ));
+    }
+    for saml in settings.saml.iter().filter(|saml| saml.common.enabled) {
+        modules.push((saml.common.order, Box::new(SAMLLoginModule::new(saml))));
+    }
+    for client_cert in settings
+        .client_cert
+        .iter()
+        .filter(|client_cert| client_cert.common.enabled)
+    {
+        modules.push((
+            client_cert.common.order,
+            Box::new(ClientCertLoginModule::new(client_cert)?),
+        ));
+    }
+
+    // Stable sort, so modules with the same order keep the order above
+    modules.sort_by_key(|(order, _)| *order);
+
+    let mut builder = ModuleBuilder::new();
+    for (_, module) in modules {
+        builder.register_module(module);
+    }
+    builder.build()
+}
+
+/// Stores a login attempt of any module, returning whether it could be written
+async fn store_login_attempt(
+    user_id: &str,
+    success: bool,
+    remote_addr: String,
+    request_id: &RequestId,
+    db_conn: &DbConn,
+) -> bool {
+    let login_entry = LoginEntryActiveModel {
+        username: Set(user_id.to_owned()),
+        timestamp: Set(database::now()),
+        success: Set(success),
+        ip_address: Set(remote_addr),
+        request_id: Set(Some(request_id.to_string())),
+        ..Default::default()
+    };
+
+    match LoginEntryEntity::insert(login_entry).exec(db_conn).await {
+        Ok(_) => true,
+        Err(e) => {
+            error!(
+                "Error inserting login entry (user_id='{user_id}', success={success}): {e}",
+                user_id = user_id,
+                success = success,
+                e = e
+            );
+            false
+        }
+    }
+}
@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 use actix_session::Session;
 use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use mordor_macros::LoginModuleMetadata;
 use reqwest::header::HeaderValue;
 use sea_orm::{DbConn, EntityTrait, Set};
 use tracing::{error, warn};
@@ -9,10 +10,15 @@ use url::Url;
 
 use crate::{
     controllers::{
+        log_in,
+        request_id::RequestId,
         structures::{FormDataSAMLResponse, QueryDataRedirect},
-        LoginModule, USERNAME_SESSION_KEY,
+        LoginModule, ModuleInfo, ModuleName,
+    },
+    database::{
+        self,
+        entity::{LoginEntryActiveModel, LoginEntryEntity},
     },
-    database::entity::{LoginEntryActiveModel, LoginEntryEntity},
     errors::{AppError, SessionError},
     settings::modules::saml,
     utils::get_http_client,
@@ -21,49 +27,61 @@ use crate::{
 pub const SAML_PROXY_COOKIE: &str = "saml-proxy";
 pub const SAML_USER_ALIAS: &str = "[SAML-USER]";
 
+/// State of a single SAML module instance, shared with its request handlers
 #[derive(Debug)]
+struct SAMLInstance {
+    config: saml::Settings,
+    /// Session key of the upstream's cookie, so that instances don't overwrite each other's
+    proxy_cookie_key: String,
+}
+
+#[derive(Debug, LoginModuleMetadata)]
+#[login_module(
+    name = "NetIDAuth",
+    subpath = "/saml",
+    display_name = "NetID Login (TU Delft)"
+)]
 pub struct SAMLLoginModule {
-    config: Arc<saml::Settings>,
+    instance: Arc<SAMLInstance>,
+    info: ModuleInfo,
 }
 
 impl SAMLLoginModule {
     pub fn new(saml_config: &saml::Settings) -> Self {
+        let info = ModuleInfo::new::<Self>(&saml_config.common);
         Self {
-            config: Arc::new(saml_config.clone()),
+            instance: Arc::new(SAMLInstance {
+                config: saml_config.clone(),
+                proxy_cookie_key: format!("{SAML_PROXY_COOKIE}:{}", info.name),
+            }),
+            info,
         }
     }
 }
 
 impl LoginModule for SAMLLoginModule {
-    fn name(&self) -> &'static str {
-        "NetIDAuth"
-    }
-
-    fn subpath(&self) -> &'static str {
-        "/saml"
+    fn info(&self) -> &ModuleInfo {
+        &self.info
     }
 
     fn register_controller(&self, svc_cfg: &mut actix_web::web::ServiceConfig) {
         svc_cfg
             .service(start)
             .service(consume)
-            .app_data(web::Data::<Arc<saml::Settings>>::new(self.config.clone()));
-    }
-
-    fn display_name(&self) -> &'static str {
-        "NetID Login (TU Delft)"
+            .app_data(web::Data::from(self.instance.clone()));
     }
 }
 
 #[get("/")]
 async fn start(
-    config: web::Data<Arc<saml::Settings>>,
+    instance: web::Data<SAMLInstance>,
     query_data: web::Query<QueryDataRedirect>,
     session: Session,
+    request_id: RequestId,
 ) -> Result<impl Responder, AppError> {
-    let client = get_http_client()?;
+    let client = get_http_client(&request_id)?;
 
-    let saml_url = config.upstream_url.join("/start").map_err(|err| {
+    let saml_url = instance.config.upstream_url.join("/start").map_err(|err| {
         error!("Error parsing upstream URL: {:?}", err);
         AppError::Internal
     })?;
@@ -105,10 +123,12 @@ async fn start(
         }
     };
 
-    session.insert(SAML_PROXY_COOKIE, cookie).map_err(|err| {
-        error!("Error inserting cookie into session: {:?}", err);
-        SessionError::SetError(err)
-    })?;
+    session
+        .insert(&instance.proxy_cookie_key, cookie)
+        .map_err(|err| {
+            error!("Error inserting cookie into session: {:?}", err);
+            SessionError::SetError(err)
+        })?;
 
     Ok(HttpResponse::TemporaryRedirect()
         .append_header(("Location", location))
@@ -117,25 +137,31 @@ async fn start(
 
 #[post("/consume/")]
 async fn consume(
-    config: web::Data<Arc<saml::Settings>>,
+    instance: web::Data<SAMLInstance>,
     db_conn: web::Data<DbConn>,
     query_data: web::Form<FormDataSAMLResponse>,
     session: Session,
+    module: web::Data<ModuleName>,
     req: HttpRequest,
+    request_id: RequestId,
 ) -> Result<impl Responder, AppError> {
     // Get the SAML proxy cookie from the session
     let saml_cookie = session
-        .get::<String>(SAML_PROXY_COOKIE)
+        .get::<String>(&instance.proxy_cookie_key)
         .map_err(Into::<SessionError>::into)?
         .ok_or(AppError::NotAuthenticated)?;
 
     // Construct the HTTP client
-    let client = get_http_client()?;
+    let client = get_http_client(&request_id)?;
 
-    let saml_url = config.upstream_url.join("/consume").map_err(|err| {
-        error!("Error parsing upstream URL: {:?}", err);
-        AppError::Internal
-    })?;
+    let saml_url = instance
+        .config
+        .upstream_url
+        .join("/consume")
+        .map_err(|err| {
+            error!("Error parsing upstream URL: {:?}", err);
+            AppError::Internal
+        })?;
 
     let resp = client
         .post(saml_url)
@@ -157,7 +183,7 @@ async fn consume(
         return Err(AppError::UnknownUpstreamError);
     }
 
-    if let Err(err) = session.insert(USERNAME_SESSION_KEY, SAML_USER_ALIAS) {
+    if let Err(err) = log_in(&session, SAML_USER_ALIAS, &module) {
         error!("Error inserting username into session: {:?}", err);
         return Err(AppError::Internal);
     }
@@ -165,10 +191,13 @@ async fn consume(
     // Update the cookie received from the upstream
     match resp.headers().get("Set-Cookie").map(HeaderValue::to_str) {
         Some(Ok(val)) => {
-            if let Err(err) = session.insert(SAML_PROXY_COOKIE, val).map_err(|err| {
-                error!("Error inserting cookie into session: {:?}", err);
-                SessionError::SetError(err)
-            }) {
+            if let Err(err) = session
+                .insert(&instance.proxy_cookie_key, val)
+                .map_err(|err| {
+                    error!("Error inserting cookie into session: {:?}", err);
+                    SessionError::SetError(err)
+                })
+            {
                 error!("Error inserting cookie into session: {:?}", err);
             }
         }
@@ -208,8 +237,10 @@ async fn consume(
         .to_owned();
     let login_entry = LoginEntryActiveModel {
         username: Set(SAML_USER_ALIAS.to_owned()),
+        timestamp: Set(database::now()),
         success: Set(true),
         ip_address: Set(remote_addr),
+        request_id: Set(Some(request_id.to_string())),
         ..Default::default()
     };
     if let Err(err) = LoginEntryEntity::insert(login_entry)
@@ -0,0 +1,96 @@
+use std::future::{ready, Future, Ready};
+
+use actix_web::{
+    dev::{Payload, Service, ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    Error, FromRequest, HttpMessage, HttpRequest,
+};
+use tracing::{debug, info_span, Instrument};
+
+/// Header carrying the correlation ID, as set by e.g. nginx with `proxy_set_header X-Request-Id
+/// $request_id`
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Incoming IDs longer than this are replaced, so clients can't flood the logs
+const MAX_LENGTH: usize = 128;
+
+/// Correlation ID of a request, shared with the reverse proxy in front of us and the upstreams we
+/// call on its behalf
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    /// 128 random bits in hex, the same format nginx uses for `$request_id`
+    fn generate() -> Self {
+        Self(format!("{:032x}", rand::random::<u128>()))
+    }
+
+    /// Takes the ID from the request header if it is safe to log and forward
+    fn from_header(value: &HeaderValue) -> Option<Self> {
+        let value = value.to_str().ok()?;
+        let valid = !value.is_empty()
+            && value.len() <= MAX_LENGTH
+            && value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.:".contains(c));
+        valid.then(|| Self(value.to_owned()))
+    }
+
+    /// The ID assigned to a request, generating one if it didn't pass through `assign`
+    pub fn of(req: &impl HttpMessage) -> Self {
+        req.extensions()
+            .get::<Self>()
+            .cloned()
+            .unwrap_or_else(Self::generate)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromRequest for RequestId {
+    type Error = Error;
+    type Future = Ready<Result<Self, Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(Self::of(req)))
+    }
+}
+
+/// Middleware assigning each request an ID, which is logged with everything done for the request
+/// and returned in the `X-Request-Id` response header
+pub fn assign<S, B>(
+    req: ServiceRequest,
+    service: &S,
+) -> impl Future<Output = Result<ServiceResponse<B>, Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+    let incoming = req.headers().get(REQUEST_ID_HEADER);
+    let request_id = match incoming.map(RequestId::from_header) {
+        Some(Some(request_id)) => request_id,
+        Some(None) => {
+            debug!("Ignoring invalid {REQUEST_ID_HEADER} header");
+            RequestId::generate()
+        }
+        None => RequestId::generate(),
+    };
+    req.extensions_mut().insert(request_id.clone());
+
+    let span = info_span!("request", request_id = %request_id);
+    let response = service.call(req).instrument(span);
+    async move {
+        let mut response = response.await?;
+        if let Ok(value) = HeaderValue::from_str(request_id.as_str()) {
+            response.headers_mut().insert(REQUEST_ID_HEADER, value);
+        }
+        Ok(response)
+    }
+}
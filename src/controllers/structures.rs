@@ -17,3 +17,17 @@ pub struct FormDataSAMLResponse {
     #[serde(rename = "RelayState")]
     pub relay_state: String,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct FormDataNewToken {
+    pub name: String,
+    /// Whitespace separated
+    pub scopes: String,
+    pub lifetime_days: u32,
+    pub csrf_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FormDataCsrf {
+    pub csrf_token: String,
+}
@@ -0,0 +1,297 @@
+use std::sync::Arc;
+
+use actix_session::Session;
+use actix_web::{
+    get,
+    http::{
+        header::{self, ContentType},
+        StatusCode,
+    },
+    post, web, HttpRequest, HttpResponse,
+};
+use ring::constant_time::verify_slices_are_equal;
+use sea_orm::DbConn;
+use serde::Serialize;
+use tera::Context;
+use tracing::{error, info, warn};
+
+use crate::{
+    controllers::{
+        modules::saml_auth::SAML_USER_ALIAS,
+        structures::{FormDataCsrf, FormDataNewToken},
+        USERNAME_SESSION_KEY,
+    },
+    database::{api_tokens, entity::ApiTokenModel},
+    errors::{AppError, SessionError},
+    settings::Settings,
+    templates::Templates,
+    utils::format_timestamp,
+};
+
+/// Session key of the token that the forms on the tokens page must send back
+const CSRF_SESSION_KEY: &str = "csrf_token";
+
+#[derive(Debug, Serialize)]
+struct TokenData {
+    id: i32,
+    name: String,
+    scopes: String,
+    created_at: String,
+    expires_at: Option<String>,
+    last_used_at: Option<String>,
+}
+
+impl From<ApiTokenModel> for TokenData {
+    fn from(model: ApiTokenModel) -> Self {
+        Self {
+            id: model.id,
+            name: model.name,
+            scopes: model.scopes,
+            created_at: format_timestamp(model.created_at),
+            expires_at: model.expires_at.map(format_timestamp),
+            last_used_at: model.last_used_at.map(format_timestamp),
+        }
+    }
+}
+
+/// Lists the user's tokens, along with a form to create a new one
+#[get("/")]
+pub async fn index(
+    req: HttpRequest,
+    db_conn: web::Data<DbConn>,
+    configuration: web::Data<Arc<Settings>>,
+    templates: web::Data<Templates>,
+    session: Session,
+) -> Result<HttpResponse, AppError> {
+    let base_path = configuration.http.base_path();
+    let Some(user_id) = current_user(&session)? else {
+        return Ok(HttpResponse::SeeOther()
+            .append_header((
+                "Location",
+                format!("{base_path}/login/?redirect={base_path}/tokens/"),
+            ))
+            .finish());
+    };
+
+    let language = templates.language(&req, &session);
+    let ctx = templates.context(&req, &language);
+    render(
+        &db_conn,
+        &configuration,
+        &templates,
+        &session,
+        &user_id,
+        StatusCode::OK,
+        ctx,
+    )
+    .await
+}
+
+#[post("/")]
+pub async fn create(
+    req: HttpRequest,
+    db_conn: web::Data<DbConn>,
+    configuration: web::Data<Arc<Settings>>,
+    templates: web::Data<Templates>,
+    session: Session,
+    form: web::Form<FormDataNewToken>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = current_user(&session)?.ok_or(AppError::NotAuthenticated)?;
+    check_csrf(&req, &configuration, &session, &form.csrf_token)?;
+    let settings = &configuration.api_tokens;
+
+    let name = form.name.trim();
+    let scopes: Vec<String> = form.scopes.split_whitespace().map(str::to_owned).collect();
+    let language = templates.language(&req, &session);
+    let mut ctx = templates.context(&req, &language);
+
+    let error = if name.is_empty() {
+        Some(templates.translate(&language, "tokens_error_name"))
+    } else if scopes.is_empty() || !scopes.iter().all(|scope| api_tokens::is_valid_scope(scope)) {
+        Some(templates.translate(&language, "tokens_error_scopes"))
+    } else if form.lifetime_days == 0 {
+        Some(templates.translate(&language, "tokens_error_lifetime"))
+    } else {
+        match settings.max_lifetime_days {
+            Some(max) if form.lifetime_days > max => Some(
+                templates
+                    .translate(&language, "tokens_error_max_lifetime")
+                    .replace("{max}", &max.to_string()),
+            ),
+            _ => None,
+        }
+    };
+    if let Some(error) = error {
+        ctx.insert("error", &error);
+        return render(
+            &db_conn,
+            &configuration,
+            &templates,
+            &session,
+            &user_id,
+            StatusCode::BAD_REQUEST,
+            ctx,
+        )
+        .await;
+    }
+
+    let expires_at = api_tokens::expires_in(form.lifetime_days);
+    let (token, model) = api_tokens::create(&db_conn, &user_id, name, &scopes, Some(expires_at))
+        .await
+        .map_err(|e| {
+            error!("Error creating API token: {e}");
+            AppError::Internal
+        })?;
+    info!("User '{user_id}' created API token {}", model.id);
+
+    ctx.insert("new_token", &token);
+    render(
+        &db_conn,
+        &configuration,
+        &templates,
+        &session,
+        &user_id,
+        StatusCode::OK,
+        ctx,
+    )
+    .await
+}
+
+#[post("/{id}/revoke/")]
+pub async fn revoke(
+    req: HttpRequest,
+    db_conn: web::Data<DbConn>,
+    configuration: web::Data<Arc<Settings>>,
+    session: Session,
+    id: web::Path<i32>,
+    form: web::Form<FormDataCsrf>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = current_user(&session)?.ok_or(AppError::NotAuthenticated)?;
+    check_csrf(&req, &configuration, &session, &form.csrf_token)?;
+    let id = id.into_inner();
+
+    // Only the user's own tokens can be revoked here
+    match api_tokens::revoke(&db_conn, id, Some(&user_id)).await {
+        Ok(true) => info!("User '{user_id}' revoked API token {id}"),
+        Ok(false) => {}
+        Err(e) => {
+            error!("Error revoking API token {id}: {e}");
+            return Err(AppError::Internal);
+        }
+    }
+
+    Ok(HttpResponse::SeeOther()
+        .append_header((
+            "Location",
+            format!("{}/tokens/", configuration.http.base_path()),
+        ))
+        .finish())
+}
+
+/// Returns the logged in user, if any. Users logged in through SAML share a single alias, so
+/// they can't have personal tokens.
+fn current_user(session: &Session) -> Result<Option<String>, AppError> {
+    let user_id: Option<String> = session
+        .get(USERNAME_SESSION_KEY)
+        .map_err(Into::<SessionError>::into)?;
+    if user_id.as_deref() == Some(SAML_USER_ALIAS) {
+        return Err(AppError::Forbidden);
+    }
+    Ok(user_id)
+}
+
+/// Returns the session's CSRF token, creating one on first use
+fn csrf_token(session: &Session) -> Result<String, AppError> {
+    let token: Option<String> = session
+        .get(CSRF_SESSION_KEY)
+        .map_err(Into::<SessionError>::into)?;
+    if let Some(token) = token {
+        return Ok(token);
+    }
+
+    let token = hex::encode(rand::random::<[u8; 32]>());
+    session
+        .insert(CSRF_SESSION_KEY, &token)
+        .map_err(Into::<SessionError>::into)?;
+    Ok(token)
+}
+
+/// Rejects forms submitted by other sites, which browsers may send with the session cookie
+/// depending on `session.same_site`
+fn check_csrf(
+    req: &HttpRequest,
+    configuration: &Settings,
+    session: &Session,
+    form_token: &str,
+) -> Result<(), AppError> {
+    // Browsers that don't send `Origin` still have to know the token
+    if let Some(origin) = req.headers().get(header::ORIGIN) {
+        let expected = match &configuration.http.public_url {
+            Some(public_url) => public_url.origin().ascii_serialization(),
+            None => {
+                let connection_info = req.connection_info();
+                format!("{}://{}", connection_info.scheme(), connection_info.host())
+            }
+        };
+        if origin.to_str().ok() != Some(expected.as_str()) {
+            warn!("Rejected tokens form from origin {origin:?}, expected '{expected}'");
+            return Err(AppError::Forbidden);
+        }
+    }
+
+    let token: Option<String> = session
+        .get(CSRF_SESSION_KEY)
+        .map_err(Into::<SessionError>::into)?;
+    let valid = token.is_some_and(|token| {
+        verify_slices_are_equal(token.as_bytes(), form_token.as_bytes()).is_ok()
+    });
+    if !valid {
+        warn!("Rejected tokens form without a valid CSRF token");
+        return Err(AppError::Forbidden);
+    }
+
+    Ok(())
+}
+
+async fn render(
+    db_conn: &DbConn,
+    configuration: &Settings,
+    templates: &Templates,
+    session: &Session,
+    user_id: &str,
+    status: StatusCode,
+    mut ctx: Context,
+) -> Result<HttpResponse, AppError> {
+    let tokens: Vec<TokenData> = api_tokens::list(db_conn, Some(user_id))
+        .await
+        .map_err(|e| {
+            error!("Error listing API tokens: {e}");
+            AppError::Internal
+        })?
+        .into_iter()
+        .map(TokenData::from)
+        .collect();
+
+    ctx.insert("base_path", configuration.http.base_path());
+    ctx.insert("username", user_id);
+    ctx.insert("tokens", &tokens);
+    ctx.insert("csrf_token", &csrf_token(session)?);
+    ctx.insert(
+        "default_lifetime_days",
+        &configuration.api_tokens.default_lifetime_days,
+    );
+    ctx.insert(
+        "max_lifetime_days",
+        &configuration.api_tokens.max_lifetime_days,
+    );
+
+    match templates.render("tokens.html", &ctx) {
+        Ok(s) => Ok(HttpResponse::build(status)
+            .content_type(ContentType::html())
+            .body(s)),
+        Err(e) => {
+            error!("Error rendering template: {:?}", e);
+            Err(AppError::from(e))
+        }
+    }
+}
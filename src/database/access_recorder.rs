@@ -0,0 +1,219 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use sea_orm::{DbConn, EntityTrait, Set};
+use time::OffsetDateTime;
+use tokio::{
+    sync::{
+        mpsc::{self, error::TrySendError},
+        oneshot,
+    },
+    task::JoinHandle,
+};
+use tracing::{debug, error, warn};
+
+use crate::{
+    controllers::ForwardedRequest,
+    database::entity::{AccessEntryActiveModel, AccessEntryEntity},
+    settings::access_entries::Settings,
+};
+
+/// User, host and URI of an access
+type DedupKey = (String, Option<String>, Option<String>);
+
+#[derive(Debug, Clone)]
+pub struct AccessRecord {
+    pub username: String,
+    pub timestamp: OffsetDateTime,
+    pub request: ForwardedRequest,
+}
+
+impl AccessRecord {
+    /// Accesses with the same key are considered duplicates of each other
+    fn dedup_key(&self) -> DedupKey {
+        (
+            self.username.clone(),
+            self.request.host.clone(),
+            self.request.uri.clone(),
+        )
+    }
+
+    /// Drops the request details that should not be stored
+    fn redact(&mut self, settings: &Settings) {
+        let request = &mut self.request;
+        for (keep, field) in [
+            (settings.record_host, &mut request.host),
+            (settings.record_uri, &mut request.uri),
+            (settings.record_method, &mut request.method),
+            (settings.record_client_ip, &mut request.client_ip),
+            (settings.record_user_agent, &mut request.user_agent),
+        ] {
+            if !keep {
+                *field = None;
+            }
+        }
+    }
+}
+
+impl From<AccessRecord> for AccessEntryActiveModel {
+    fn from(record: AccessRecord) -> Self {
+        AccessEntryActiveModel {
+            username: Set(record.username),
+            timestamp: Set(record.timestamp),
+            host: Set(record.request.host),
+            uri: Set(record.request.uri),
+            method: Set(record.request.method),
+            client_ip: Set(record.request.client_ip),
+            user_agent: Set(record.request.user_agent),
+            ..Default::default()
+        }
+    }
+}
+
+/// Queues access entries in memory and writes them to the database in batches, off the request
+/// path. Cheap to clone; all clones feed the same writer task.
+#[derive(Debug, Clone)]
+pub struct AccessRecorder {
+    sender: mpsc::Sender<AccessRecord>,
+    settings: Arc<Settings>,
+}
+
+/// Owned by whoever started the recorder, used to flush pending entries on shutdown
+pub struct AccessRecorderHandle {
+    shutdown: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl AccessRecorder {
+    pub fn start(db_conn: DbConn, settings: Settings) -> (Self, AccessRecorderHandle) {
+        let (sender, receiver) = mpsc::channel(settings.queue_size);
+        let (shutdown, shutdown_receiver) = oneshot::channel();
+        let settings = Arc::new(settings);
+        let task = tokio::spawn(run(db_conn, settings.clone(), receiver, shutdown_receiver));
+
+        (
+            Self { sender, settings },
+            AccessRecorderHandle { shutdown, task },
+        )
+    }
+
+    /// Queues an entry without waiting. If the queue is full, the entry is dropped.
+    pub fn record(&self, mut record: AccessRecord) {
+        record.redact(&self.settings);
+        match self.sender.try_send(record) {
+            Ok(()) => {}
+            Err(TrySendError::Full(record)) => {
+                warn!(
+                    "Access entry queue is full, dropping entry (user_id='{}')",
+                    record.username
+                );
+            }
+            Err(TrySendError::Closed(record)) => {
+                error!(
+                    "Access entry recorder is not running, dropping entry (user_id='{}')",
+                    record.username
+                );
+            }
+        }
+    }
+}
+
+impl AccessRecorderHandle {
+    /// Stops accepting new entries and waits until all queued ones have been written
+    pub async fn shutdown(self) {
+        let _ = self.shutdown.send(());
+        if let Err(e) = self.task.await {
+            error!("Access entry recorder task failed: {e}");
+        }
+    }
+}
+
+struct Deduplicator {
+    window: Option<time::Duration>,
+    last_seen: HashMap<DedupKey, OffsetDateTime>,
+}
+
+impl Deduplicator {
+    fn new(window_secs: Option<u64>) -> Self {
+        Self {
+            window: window_secs.map(|secs| time::Duration::seconds(secs as i64)),
+            last_seen: HashMap::new(),
+        }
+    }
+
+    /// Returns whether the record should be stored, i.e. it is not a duplicate of a recent one
+    fn accept(&mut self, record: &AccessRecord) -> bool {
+        let Some(window) = self.window else {
+            return true;
+        };
+
+        let key = record.dedup_key();
+        match self.last_seen.get(&key) {
+            Some(last) if record.timestamp - *last < window => false,
+            _ => {
+                self.last_seen.insert(key, record.timestamp);
+                true
+            }
+        }
+    }
+
+    /// Forgets accesses that are too old to cause any further duplicates
+    fn expire(&mut self) {
+        if let Some(window) = self.window {
+            let now = OffsetDateTime::now_utc();
+            self.last_seen.retain(|_, last| now - *last < window);
+        }
+    }
+}
+
+async fn run(
+    db_conn: DbConn,
+    settings: Arc<Settings>,
+    mut receiver: mpsc::Receiver<AccessRecord>,
+    mut shutdown: oneshot::Receiver<()>,
+) {
+    let mut batch: Vec<AccessRecord> = Vec::with_capacity(settings.batch_size);
+    let mut deduplicator = Deduplicator::new(settings.dedup_window_secs);
+    let mut interval = tokio::time::interval(Duration::from_millis(settings.flush_interval_ms));
+    let mut shutting_down = false;
+
+    loop {
+        tokio::select! {
+            record = receiver.recv() => match record {
+                Some(record) => {
+                    if deduplicator.accept(&record) {
+                        batch.push(record);
+                    }
+                    if batch.len() >= settings.batch_size {
+                        flush(&db_conn, &mut batch).await;
+                    }
+                }
+                // All senders are gone or the channel was closed and drained
+                None => break,
+            },
+            _ = interval.tick() => {
+                flush(&db_conn, &mut batch).await;
+                deduplicator.expire();
+            },
+            _ = &mut shutdown, if !shutting_down => {
+                debug!("Shutting down access entry recorder");
+                shutting_down = true;
+                receiver.close();
+            },
+        }
+    }
+
+    flush(&db_conn, &mut batch).await;
+}
+
+async fn flush(db_conn: &DbConn, batch: &mut Vec<AccessRecord>) {
+    if batch.is_empty() {
+        return;
+    }
+
+    let count = batch.len();
+    let entries = batch.drain(..).map(AccessEntryActiveModel::from);
+    match AccessEntryEntity::insert_many(entries).exec(db_conn).await {
+        Ok(_) => debug!("Stored {count} access entries"),
+        Err(e) => error!("Error inserting {count} access entries: {e}"),
+    }
+}
@@ -0,0 +1,278 @@
+use rand::RngCore;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DbConn, DbErr, EntityTrait, IntoActiveModel, QueryFilter,
+    QueryOrder, Set,
+};
+use sha2::{Digest, Sha256};
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    controllers::ForwardedRequest,
+    database::{
+        self,
+        entity::{ApiTokenActiveModel, ApiTokenColumn, ApiTokenEntity, ApiTokenModel},
+    },
+};
+
+/// Distinguishes mordor's tokens from bearer tokens meant for the protected applications
+pub const TOKEN_PREFIX: &str = "mdr_";
+/// Scope that matches every request
+pub const SCOPE_ANY: &str = "*";
+/// `last_used_at` is only updated once per interval, to avoid a write on every request
+const LAST_USED_RESOLUTION: Duration = Duration::minutes(1);
+
+/// Creates a token, returning it in plain text along with the stored model. Only a hash of the
+/// token is stored, so it can't be retrieved later.
+pub async fn create(
+    db_conn: &DbConn,
+    username: &str,
+    name: &str,
+    scopes: &[String],
+    expires_at: Option<OffsetDateTime>,
+) -> Result<(String, ApiTokenModel), DbErr> {
+    let mut secret = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut secret);
+    let token = format!("{TOKEN_PREFIX}{}", hex::encode(secret));
+
+    let model = ApiTokenActiveModel {
+        username: Set(username.to_owned()),
+        name: Set(name.to_owned()),
+        token_hash: Set(hash(&token)),
+        scopes: Set(scopes.join(" ")),
+        created_at: Set(database::now()),
+        expires_at: Set(expires_at),
+        last_used_at: Set(None),
+        ..Default::default()
+    }
+    .insert(db_conn)
+    .await?;
+
+    Ok((token, model))
+}
+
+/// Lists all tokens, or only those of `username`, oldest first
+pub async fn list(db_conn: &DbConn, username: Option<&str>) -> Result<Vec<ApiTokenModel>, DbErr> {
+    let mut query = ApiTokenEntity::find().order_by_asc(ApiTokenColumn::Id);
+    if let Some(username) = username {
+        query = query.filter(ApiTokenColumn::Username.eq(username));
+    }
+    query.all(db_conn).await
+}
+
+/// Deletes a token, optionally only if it belongs to `username`. Returns whether it existed.
+pub async fn revoke(db_conn: &DbConn, id: i32, username: Option<&str>) -> Result<bool, DbErr> {
+    let mut query = ApiTokenEntity::delete_many().filter(ApiTokenColumn::Id.eq(id));
+    if let Some(username) = username {
+        query = query.filter(ApiTokenColumn::Username.eq(username));
+    }
+    Ok(query.exec(db_conn).await?.rows_affected > 0)
+}
+
+/// Looks up a token and checks that it hasn't expired and that one of its scopes covers the
+/// request
+pub async fn authenticate(
+    db_conn: &DbConn,
+    token: &str,
+    request: &ForwardedRequest,
+) -> Result<Option<ApiTokenModel>, DbErr> {
+    let Some(model) = ApiTokenEntity::find()
+        .filter(ApiTokenColumn::TokenHash.eq(hash(token)))
+        .one(db_conn)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    let now = database::now();
+    if model.expires_at.is_some_and(|expires_at| expires_at <= now) {
+        return Ok(None);
+    }
+    if !model
+        .scopes
+        .split_whitespace()
+        .any(|scope| scope_matches(scope, request))
+    {
+        return Ok(None);
+    }
+
+    if model
+        .last_used_at
+        .is_none_or(|last_used_at| now - last_used_at >= LAST_USED_RESOLUTION)
+    {
+        let mut active_model = model.clone().into_active_model();
+        active_model.last_used_at = Set(Some(now));
+        active_model.update(db_conn).await?;
+    }
+
+    Ok(Some(model))
+}
+
+/// Expiry time of a token created now with the given lifetime
+pub fn expires_in(days: u32) -> OffsetDateTime {
+    database::now() + Duration::days(days.into())
+}
+
+/// Scopes are `*`, a host (`example.com`) or a host with a path prefix (`example.com/courses`).
+/// Hosts are matched against `X-Forwarded-Host`, which nginx doesn't set on its own.
+pub fn is_valid_scope(scope: &str) -> bool {
+    !scope.is_empty() && !scope.starts_with('/') && !scope.chars().any(char::is_whitespace)
+}
+
+fn scope_matches(scope: &str, request: &ForwardedRequest) -> bool {
+    if scope == SCOPE_ANY {
+        return true;
+    }
+
+    let (scope_host, scope_path) = match scope.find('/') {
+        Some(index) => scope.split_at(index),
+        None => (scope, ""),
+    };
+
+    let Some(host) = &request.host else {
+        return false;
+    };
+    // Unless the scope names a port, it applies to all ports of the host
+    let host_matches = host.eq_ignore_ascii_case(scope_host)
+        || host
+            .rsplit_once(':')
+            .is_some_and(|(hostname, _)| hostname.eq_ignore_ascii_case(scope_host));
+
+    host_matches
+        && (scope_path.is_empty()
+            || request
+                .uri
+                .as_deref()
+                .is_some_and(|uri| path_matches(scope_path, uri)))
+}
+
+/// Whether the URI is the scope's path or below it, so `/courses` doesn't match `/coursesX`
+fn path_matches(scope_path: &str, uri: &str) -> bool {
+    let scope_path = scope_path.trim_end_matches('/');
+    match uri.strip_prefix(scope_path) {
+        Some(rest) => rest.is_empty() || rest.starts_with(['/', '?']),
+        None => false,
+    }
+}
+
+fn hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(host: Option<&str>, uri: Option<&str>) -> ForwardedRequest {
+        ForwardedRequest {
+            host: host.map(ToOwned::to_owned),
+            uri: uri.map(ToOwned::to_owned),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn wildcard_matches_everything() {
+        assert!(scope_matches("*", &request(Some("example.com"), Some("/"))));
+        assert!(scope_matches("*", &request(None, None)));
+    }
+
+    #[test]
+    fn host_scope() {
+        let scope = "example.com";
+        assert!(scope_matches(
+            scope,
+            &request(Some("example.com"), Some("/a"))
+        ));
+        assert!(scope_matches(scope, &request(Some("example.com"), None)));
+        assert!(!scope_matches(
+            scope,
+            &request(Some("example.org"), Some("/a"))
+        ));
+        assert!(!scope_matches(
+            scope,
+            &request(Some("sub.example.com"), Some("/"))
+        ));
+        assert!(!scope_matches(
+            scope,
+            &request(Some("example.com.evil"), Some("/"))
+        ));
+    }
+
+    #[test]
+    fn host_is_case_insensitive() {
+        assert!(scope_matches(
+            "Example.COM",
+            &request(Some("example.com"), None)
+        ));
+        assert!(scope_matches(
+            "example.com",
+            &request(Some("EXAMPLE.com:8443"), None)
+        ));
+    }
+
+    #[test]
+    fn host_with_port() {
+        // Scopes without a port apply to every port
+        assert!(scope_matches(
+            "example.com",
+            &request(Some("example.com:8080"), None)
+        ));
+        assert!(scope_matches(
+            "example.com:8080",
+            &request(Some("example.com:8080"), None)
+        ));
+        assert!(!scope_matches(
+            "example.com:8080",
+            &request(Some("example.com:9090"), None)
+        ));
+        assert!(!scope_matches(
+            "example.com:8080",
+            &request(Some("example.com"), None)
+        ));
+    }
+
+    #[test]
+    fn path_prefix_ends_at_a_segment() {
+        let scope = "example.com/courses";
+        let matches = |uri| scope_matches(scope, &request(Some("example.com"), Some(uri)));
+        assert!(matches("/courses"));
+        assert!(matches("/courses/"));
+        assert!(matches("/courses/math/1"));
+        assert!(matches("/courses?page=2"));
+        assert!(!matches("/coursesX"));
+        assert!(!matches("/courses-old/"));
+        assert!(!matches("/"));
+        assert!(!matches("/other/courses"));
+
+        assert!(scope_matches(
+            "example.com/courses/",
+            &request(Some("example.com"), Some("/courses/math"))
+        ));
+        assert!(scope_matches(
+            "example.com/",
+            &request(Some("example.com"), Some("/anything"))
+        ));
+    }
+
+    #[test]
+    fn missing_host_or_uri() {
+        assert!(!scope_matches("example.com", &request(None, Some("/"))));
+        assert!(!scope_matches(
+            "example.com/courses",
+            &request(None, Some("/courses"))
+        ));
+        assert!(!scope_matches(
+            "example.com/courses",
+            &request(Some("example.com"), None)
+        ));
+    }
+
+    #[test]
+    fn valid_scopes() {
+        assert!(is_valid_scope("*"));
+        assert!(is_valid_scope("example.com/courses"));
+        assert!(!is_valid_scope(""));
+        assert!(!is_valid_scope("/courses"));
+        assert!(!is_valid_scope("example.com /courses"));
+    }
+}
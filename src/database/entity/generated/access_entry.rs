@@ -9,7 +9,12 @@ pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
     pub username: String,
-    pub timestamp: String,
+    pub timestamp: TimeDateTimeWithTimeZone,
+    pub host: Option<String>,
+    pub uri: Option<String>,
+    pub method: Option<String>,
+    pub client_ip: Option<String>,
+    pub user_agent: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -9,9 +9,10 @@ pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
     pub username: String,
-    pub timestamp: String,
+    pub timestamp: TimeDateTimeWithTimeZone,
     pub success: bool,
     pub ip_address: String,
+    pub request_id: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
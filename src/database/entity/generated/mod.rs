@@ -3,5 +3,6 @@
 pub mod prelude;
 
 pub mod access_entry;
+pub mod api_token;
 pub mod basic_login_user;
 pub mod login_entry;
@@ -1,5 +1,6 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.2
 
 pub use super::access_entry::Entity as AccessEntry;
+pub use super::api_token::Entity as ApiToken;
 pub use super::basic_login_user::Entity as BasicLoginUser;
 pub use super::login_entry::Entity as LoginEntry;
@@ -1,7 +1,9 @@
 use mordor_macros::export_seaorm;
 
+#[allow(unused_imports)]
 pub mod generated;
 
 export_seaorm!(generated::login_entry, "LoginEntry");
 export_seaorm!(generated::basic_login_user, "BasicLoginUser");
 export_seaorm!(generated::access_entry, "AccessEntry");
+export_seaorm!(generated::api_token, "ApiToken");
@@ -1,21 +1,37 @@
 use migration::{Migrator, MigratorTrait};
 use sea_orm::{ConnectOptions, DatabaseConnection, DbErr};
+use time::OffsetDateTime;
 use tracing::{debug, log::LevelFilter};
 
 use crate::settings::database;
 
+pub mod access_recorder;
+pub mod api_tokens;
 pub mod entity;
+pub mod retention;
 
 pub async fn init(db_config: &database::Settings) -> Result<DatabaseConnection, DbErr> {
     let mut opt = ConnectOptions::new(db_config.get_url());
     opt.sqlx_logging(true)
         .sqlx_logging_level(LevelFilter::Trace);
 
-    debug!("Connecting to database: {}", db_config.get_url());
+    debug!("Connecting to database: {}", db_config.get_redacted_url());
     let connection = sea_orm::Database::connect(opt).await?;
 
-    Migrator::up(&connection, None).await?;
-    debug!("Database migration completed");
+    if db_config.migrate {
+        Migrator::up(&connection, None).await?;
+        debug!("Database migration completed");
+    } else {
+        debug!("Skipping database migration");
+    }
 
     Ok(connection)
 }
+
+/// Current UTC time for entry timestamps. Truncated to whole seconds, so that all stored values
+/// share the same textual representation on SQLite and compare correctly.
+pub fn now() -> OffsetDateTime {
+    OffsetDateTime::now_utc()
+        .replace_nanosecond(0)
+        .expect("0 is a valid nanosecond value")
+}
@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use sea_orm::{ColumnTrait, DbConn, DbErr, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use tracing::{debug, error, info};
+
+use crate::{
+    database::{
+        self,
+        entity::{AccessEntryColumn, AccessEntryEntity, LoginEntryColumn, LoginEntryEntity},
+    },
+    settings::retention::{Policy, Settings},
+};
+
+#[derive(Debug, Default)]
+pub struct PruneResult {
+    pub login_entries: u64,
+    pub access_entries: u64,
+}
+
+/// Deletes login and access entries that fall outside of the configured retention policies
+pub async fn prune(db_conn: &DbConn, settings: &Settings) -> Result<PruneResult, DbErr> {
+    Ok(PruneResult {
+        login_entries: prune_table::<LoginEntryEntity>(
+            db_conn,
+            &settings.login_entries,
+            LoginEntryColumn::Id,
+            LoginEntryColumn::Timestamp,
+        )
+        .await?,
+        access_entries: prune_table::<AccessEntryEntity>(
+            db_conn,
+            &settings.access_entries,
+            AccessEntryColumn::Id,
+            AccessEntryColumn::Timestamp,
+        )
+        .await?,
+    })
+}
+
+async fn prune_table<E: EntityTrait>(
+    db_conn: &DbConn,
+    policy: &Policy,
+    id: E::Column,
+    timestamp: E::Column,
+) -> Result<u64, DbErr> {
+    let mut deleted = 0;
+
+    if let Some(days) = policy.max_age_days {
+        let cutoff = database::now() - time::Duration::days(days.into());
+        deleted += E::delete_many()
+            .filter(timestamp.lt(cutoff))
+            .exec(db_conn)
+            .await?
+            .rows_affected;
+    }
+
+    if let Some(max_rows) = policy.max_rows {
+        // IDs are monotonically increasing, so the newest entries are the ones with the highest IDs
+        let oldest_kept: Option<i32> = E::find()
+            .select_only()
+            .column(id)
+            .order_by_desc(id)
+            .offset(max_rows.saturating_sub(1))
+            .limit(1)
+            .into_tuple()
+            .one(db_conn)
+            .await?;
+
+        if let Some(oldest_kept) = oldest_kept {
+            deleted += E::delete_many()
+                .filter(id.lt(oldest_kept))
+                .exec(db_conn)
+                .await?
+                .rows_affected;
+        }
+    }
+
+    Ok(deleted)
+}
+
+/// Periodically prunes entries, for as long as the application runs
+pub async fn run(db_conn: DbConn, settings: Settings) {
+    let mut interval = tokio::time::interval(Duration::from_secs(settings.interval_secs));
+    loop {
+        interval.tick().await;
+
+        match prune(&db_conn, &settings).await {
+            Ok(result) if result.login_entries > 0 || result.access_entries > 0 => info!(
+                "Pruned {} login entries and {} access entries",
+                result.login_entries, result.access_entries
+            ),
+            Ok(_) => debug!("No entries to prune"),
+            Err(e) => error!("Error pruning entries: {e}"),
+        }
+    }
+}
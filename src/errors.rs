@@ -9,6 +9,18 @@ use thiserror::Error;
 #[derive(Debug, Serialize)]
 pub struct APIError {
     message: String,
+    /// Set once the response leaves the handler, see `error_pages`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+impl APIError {
+    pub fn new(message: String, request_id: Option<String>) -> Self {
+        Self {
+            message,
+            request_id,
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -25,6 +37,8 @@ pub enum AppError {
     SessionError(#[from] SessionError),
     #[error("User not authenticated")]
     NotAuthenticated,
+    #[error("Not allowed")]
+    Forbidden,
     #[error("Internal error")]
     Internal,
     #[error("A templating error has occured: {0}")]
@@ -39,9 +53,7 @@ impl ResponseError for AppError {
     fn error_response(&self) -> HttpResponse {
         HttpResponse::build(self.status_code())
             .insert_header(ContentType::json())
-            .json(APIError {
-                message: self.to_string(),
-            })
+            .json(APIError::new(self.to_string(), None))
     }
 
     fn status_code(&self) -> StatusCode {
@@ -51,6 +63,7 @@ impl ResponseError for AppError {
                 SessionError::SetError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             },
             AppError::NotAuthenticated => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden => StatusCode::FORBIDDEN,
             AppError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::TemplateError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::UpstreamError(_) => StatusCode::BAD_GATEWAY,
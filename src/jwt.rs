@@ -0,0 +1,132 @@
+use std::{fs::File, io::BufReader, time::Duration};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use color_eyre::{
+    eyre::{eyre, WrapErr},
+    Result,
+};
+use ring::{
+    digest::{digest, SHA256},
+    signature::{Ed25519KeyPair, KeyPair},
+};
+use rustls_pemfile::Item;
+use serde::Serialize;
+use serde_json::json;
+use time::OffsetDateTime;
+
+use crate::settings::jwt::{Claim, Settings};
+
+/// Value of the `module` claim for users authenticated with an API token
+pub const API_TOKEN_MODULE: &str = "api_token";
+
+#[derive(Debug, Serialize)]
+struct Header<'a> {
+    alg: &'static str,
+    typ: &'static str,
+    kid: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct Claims<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iss: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aud: Option<&'a str>,
+    sub: &'a str,
+    iat: i64,
+    exp: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    groups: Option<&'a [String]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    module: Option<&'a str>,
+}
+
+/// Signs JWTs for downstream applications, which verify them with the keys published at the
+/// JWKS endpoint
+pub struct JwtIssuer {
+    key_pair: Ed25519KeyPair,
+    /// Derived from the public key, so that it changes along with the key
+    key_id: String,
+    settings: Settings,
+}
+
+impl JwtIssuer {
+    pub fn load(settings: &Settings) -> Result<Self> {
+        let path = &settings.key_file;
+        let file =
+            File::open(path).wrap_err_with(|| format!("Could not open {}", path.display()))?;
+        let pkcs8 = rustls_pemfile::read_all(&mut BufReader::new(file))
+            .wrap_err_with(|| format!("Could not parse {}", path.display()))?
+            .into_iter()
+            .find_map(|item| match item {
+                Item::PKCS8Key(der) => Some(der),
+                _ => None,
+            })
+            .ok_or_else(|| eyre!("No PKCS#8 private key found in {}", path.display()))?;
+        let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(&pkcs8)
+            .map_err(|e| eyre!("{} is not an Ed25519 private key: {e}", path.display()))?;
+
+        let fingerprint = digest(&SHA256, key_pair.public_key().as_ref());
+        let key_id = URL_SAFE_NO_PAD.encode(&fingerprint.as_ref()[..12]);
+
+        Ok(Self {
+            key_pair,
+            key_id,
+            settings: settings.clone(),
+        })
+    }
+
+    /// Response header the JWT is returned in
+    pub fn header(&self) -> &str {
+        &self.settings.header
+    }
+
+    /// Issues a JWT for `username`, who logged in through `module`
+    pub fn issue(&self, username: &str, module: Option<&str>, groups: &[String]) -> String {
+        let now = OffsetDateTime::now_utc();
+        let expires_at = now + Duration::from_secs(self.settings.ttl_secs);
+        let with_claim = |claim| self.settings.claims.contains(&claim);
+
+        let header = Header {
+            alg: "EdDSA",
+            typ: "JWT",
+            kid: &self.key_id,
+        };
+        let claims = Claims {
+            iss: self.settings.issuer.as_deref(),
+            aud: self.settings.audience.as_deref(),
+            sub: username,
+            iat: now.unix_timestamp(),
+            exp: expires_at.unix_timestamp(),
+            groups: with_claim(Claim::Groups).then_some(groups),
+            module: module.filter(|_| with_claim(Claim::Module)),
+        };
+
+        // Serializing these structs can't fail
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header).expect("serializable header")),
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).expect("serializable claims")),
+        );
+        let signature = self.key_pair.sign(signing_input.as_bytes());
+
+        format!(
+            "{signing_input}.{}",
+            URL_SAFE_NO_PAD.encode(signature.as_ref())
+        )
+    }
+
+    /// JSON Web Key Set with the public key
+    pub fn jwks(&self) -> serde_json::Value {
+        json!({
+            "keys": [{
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "use": "sig",
+                "alg": "EdDSA",
+                "kid": self.key_id,
+                "x": URL_SAFE_NO_PAD.encode(self.key_pair.public_key().as_ref()),
+            }]
+        })
+    }
+}
@@ -0,0 +1,189 @@
+use std::{fmt, net::TcpListener};
+
+#[cfg(unix)]
+use std::{
+    fs, io,
+    os::unix::{
+        fs::{FileTypeExt, PermissionsExt},
+        net::UnixListener,
+    },
+    path::PathBuf,
+};
+
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use tracing::{debug, warn};
+
+use crate::settings::http::{self, ListenAddress};
+
+/// A bound socket. Unix domain sockets are removed from the filesystem once dropped.
+#[derive(Debug)]
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix {
+        listener: UnixListener,
+        path: PathBuf,
+    },
+}
+
+impl fmt::Display for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(listener) => match listener.local_addr() {
+                Ok(addr) => write!(f, "{addr}"),
+                Err(_) => write!(f, "<unknown TCP address>"),
+            },
+            #[cfg(unix)]
+            Self::Unix { path, .. } => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Listener {
+    fn drop(&mut self) {
+        if let Self::Unix { path, .. } = self {
+            if let Err(e) = fs::remove_file(&path) {
+                warn!("Could not remove socket {}: {e}", path.display());
+            }
+        }
+    }
+}
+
+/// Binds all configured listen addresses
+pub fn bind(settings: &http::Settings) -> Result<Vec<Listener>> {
+    let unix_socket_mode = settings.unix_socket_mode()?;
+
+    settings
+        .listen_addresses()
+        .iter()
+        .map(|address| {
+            bind_address(address, unix_socket_mode)
+                .wrap_err_with(|| format!("Could not listen on {address}"))
+        })
+        .collect()
+}
+
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn bind_address(address: &ListenAddress, unix_socket_mode: Option<u32>) -> Result<Listener> {
+    match address {
+        ListenAddress::Tcp(addr) => Ok(Listener::Tcp(TcpListener::bind(addr)?)),
+        #[cfg(unix)]
+        ListenAddress::Unix(path) => {
+            // A socket left behind by a previous run would make binding fail
+            if let Ok(metadata) = fs::symlink_metadata(path) {
+                if !metadata.file_type().is_socket() {
+                    bail!("{} exists and is not a socket", path.display());
+                }
+                debug!("Removing stale socket {}", path.display());
+                fs::remove_file(path)?;
+            }
+
+            // actix-server removes the path a socket was bound to whenever a server using it
+            // stops, including the old server on every reload. Binding under a temporary name
+            // and moving the socket into place leaves it nothing to remove.
+            let Some(file_name) = path.file_name() else {
+                bail!("{} is not a file path", path.display());
+            };
+            let bind_path = path.with_file_name(format!(
+                ".{}.{}",
+                file_name.to_string_lossy(),
+                std::process::id()
+            ));
+            match fs::remove_file(&bind_path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+
+            let listener = UnixListener::bind(&bind_path)?;
+            if let Some(mode) = unix_socket_mode {
+                fs::set_permissions(&bind_path, fs::Permissions::from_mode(mode))?;
+            }
+            fs::rename(&bind_path, path)?;
+
+            Ok(Listener::Unix {
+                listener,
+                path: path.clone(),
+            })
+        }
+        #[cfg(not(unix))]
+        ListenAddress::Unix(_) => bail!("Unix domain sockets are not supported on this platform"),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::path::Path;
+
+    use actix_web::{web, App, HttpResponse, HttpServer};
+
+    use super::*;
+
+    fn socket_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("mordor-test-{}-{name}.sock", std::process::id()))
+    }
+
+    async fn serve_and_stop(listener: &UnixListener) {
+        let server = HttpServer::new(|| App::new().route("/", web::get().to(HttpResponse::Ok)))
+            .workers(1)
+            .listen_uds(listener.try_clone().unwrap())
+            .unwrap()
+            .run();
+        let handle = server.handle();
+        let task = tokio::spawn(server);
+        handle.stop(true).await;
+        task.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn unix_socket_survives_stopped_servers() {
+        let path = socket_path("reload");
+        let listener = bind_address(&ListenAddress::Unix(path.clone()), Some(0o660)).unwrap();
+        let Listener::Unix {
+            listener: unix_listener,
+            ..
+        } = &listener
+        else {
+            panic!("expected a Unix listener");
+        };
+
+        // Like a reload: the old server stops while the new one uses the same socket
+        serve_and_stop(unix_listener).await;
+        assert!(path.exists());
+        serve_and_stop(unix_listener).await;
+        assert!(path.exists());
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o660
+        );
+
+        drop(listener);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn stale_socket_is_replaced() {
+        let path = socket_path("stale");
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let listener = bind_address(&ListenAddress::Unix(path.clone()), None).unwrap();
+        assert!(path.exists());
+        drop(listener);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn other_files_are_not_replaced() {
+        let path = socket_path("file");
+        fs::write(&path, "").unwrap();
+
+        assert!(bind_address(&ListenAddress::Unix(path.clone()), None).is_err());
+        assert!(Path::new(&path).exists());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,24 +1,63 @@
-use tracing::warn;
-use tracing_subscriber::filter::FromEnvError;
+use color_eyre::{eyre::WrapErr, Result};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{fmt::writer::BoxMakeWriter, registry::LookupSpan, Layer};
 
-pub fn init() -> Result<(), FromEnvError> {
+use crate::settings::logging::{self, Format};
+
+pub fn init(settings: &logging::Settings) -> Result<()> {
     use tracing_error::ErrorLayer;
     use tracing_subscriber::prelude::*;
-    use tracing_subscriber::{fmt, EnvFilter};
-
-    let fmt_layer = fmt::layer().with_target(true);
-    let filter_layer = EnvFilter::try_from_default_env()
-        .or_else(|_| EnvFilter::try_new("info,actix_server::worker=WARN"))?;
 
     tracing_subscriber::registry()
-        .with(filter_layer)
-        .with(fmt_layer)
+        .with(settings.filter()?)
+        .with(fmt_layer(settings)?)
         .with(ErrorLayer::default())
         .init();
 
-    color_eyre::install().unwrap_or_else(|e| {
-        warn!("Colorized error reporting is not available: {e}");
-    });
-
     Ok(())
 }
+
+fn fmt_layer<S>(settings: &logging::Settings) -> Result<Box<dyn Layer<S> + Send + Sync>>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    use tracing_subscriber::fmt;
+
+    // Keep stdout free for the output of CLI commands
+    let (writer, ansi) = match &settings.file {
+        Some(file) => {
+            let rotation = match file.rotation {
+                logging::Rotation::Hourly => Rotation::HOURLY,
+                logging::Rotation::Daily => Rotation::DAILY,
+                logging::Rotation::Never => Rotation::NEVER,
+            };
+            // Pruning old files fails noisily if the directory doesn't exist yet
+            std::fs::create_dir_all(&file.directory).wrap_err_with(|| {
+                format!(
+                    "Could not create log directory {}",
+                    file.directory.display()
+                )
+            })?;
+            let mut appender = RollingFileAppender::builder()
+                .rotation(rotation)
+                .filename_prefix(&file.file_name);
+            if let Some(max_files) = file.max_files {
+                appender = appender.max_log_files(max_files);
+            }
+            (BoxMakeWriter::new(appender.build(&file.directory)?), false)
+        }
+        None => (BoxMakeWriter::new(std::io::stderr), true),
+    };
+
+    let layer = fmt::layer().with_target(true).with_writer(writer);
+    Ok(match settings.format {
+        Format::Text => layer.with_ansi(ansi).boxed(),
+        // Events are flattened, so log shippers find e.g. `message` at the top level. The request
+        // span is enough to correlate events, nested spans aren't listed.
+        Format::Json => layer
+            .json()
+            .flatten_event(true)
+            .with_span_list(false)
+            .boxed(),
+    })
+}
@@ -1,80 +1,456 @@
 use std::sync::Arc;
 
-use actix_session::SessionMiddleware;
+use actix_session::{
+    config::{BrowserSession, PersistentSession, SessionLifecycle},
+    SessionMiddleware,
+};
 use actix_web::{
-    cookie::Key,
-    middleware::{Logger, NormalizePath, TrailingSlash},
+    cookie::{time::Duration, Key},
+    dev::Server,
+    middleware::{ErrorHandlers, Logger, NormalizePath, TrailingSlash},
     web, App, HttpServer,
 };
-use clap::Parser;
-use color_eyre::Result;
-use tracing::{debug, info, trace};
+use clap::{Parser, Subcommand};
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use rustls::ServerConfig;
+use sea_orm::DatabaseConnection;
+use tracing::{debug, error, info, trace};
 
 use crate::{
-    controllers::modules::{basic_auth::BasicAuthLoginModule, saml_auth::SAMLLoginModule},
+    controllers::https_redirect::HttpsPort,
+    database::{access_recorder::AccessRecorder, api_tokens},
+    jwt::JwtIssuer,
+    listeners::Listener,
     settings::Settings,
+    templates::Templates,
+    tls::CertificateResolver,
+    utils::format_timestamp,
 };
 
 mod controllers;
 mod database;
 mod errors;
+mod jwt;
+mod listeners;
 mod logging;
+mod reload;
 mod session;
 mod settings;
+mod templates;
+mod tls;
 mod utils;
 
-pub const COOKIE_NAME: &str = "mordor-session";
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// The default access log format, followed by the request ID to correlate with the proxy's logs
+const ACCESS_LOG_FORMAT: &str =
+    r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T %{x-request-id}o"#;
 
 #[derive(Debug, Parser)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
-    /// Path to the configuration file
-    #[clap(short, long, default_value = "config.toml")]
-    config: String,
+    /// Path to the configuration file. Can be given more than once, e.g. a base configuration
+    /// followed by environment-specific overrides; later files take precedence.
+    #[clap(short, long, default_value = "config.toml", global = true)]
+    config: Vec<String>,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run the server (default)
+    Serve,
+    /// Delete login and access entries according to the configured retention policy
+    Prune,
+    /// Manage personal API tokens
+    Token {
+        #[clap(subcommand)]
+        command: TokenCommand,
+    },
+    /// Validate or generate the configuration
+    Config {
+        #[clap(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommand {
+    /// Check the configuration without starting the server; exits non-zero if it's invalid
+    Check,
+    /// Print a commented sample configuration with all settings
+    PrintDefault,
+}
+
+#[derive(Debug, Subcommand)]
+enum TokenCommand {
+    /// Create a token and print it. Only a hash is stored, so it can't be shown again.
+    Create {
+        /// User the token authenticates as
+        #[clap(long)]
+        user: String,
+        /// Description of the token, e.g. what it is used for
+        #[clap(long)]
+        name: String,
+        /// Host, optionally with a path prefix, the token is valid for. `*` matches everything.
+        #[clap(long = "scope", required = true)]
+        scopes: Vec<String>,
+        /// Days until the token expires, defaults to `api_tokens.default_lifetime_days`
+        #[clap(long, conflicts_with = "no_expiry")]
+        expires_days: Option<u32>,
+        /// Create a token that never expires
+        #[clap(long)]
+        no_expiry: bool,
+    },
+    /// List tokens
+    List {
+        /// Only list tokens of this user
+        #[clap(long)]
+        user: Option<String>,
+    },
+    /// Revoke a token by its ID
+    Revoke { id: i32 },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    logging::init()?;
-    info!("Initializing mordor version {}", VERSION);
+    color_eyre::install()?;
 
     let args = Args::parse();
+    match args.command.unwrap_or(Command::Serve) {
+        // Works on the configuration itself, so it runs before it is loaded
+        Command::Config { command } => config(command, &args.config),
+        Command::Serve => {
+            let (configuration, db_conn) = init(&args.config).await?;
+            serve(&args.config, configuration, db_conn).await
+        }
+        Command::Prune => {
+            let (configuration, db_conn) = init(&args.config).await?;
+            let result = database::retention::prune(&db_conn, &configuration.retention).await?;
+            info!(
+                "Pruned {} login entries and {} access entries",
+                result.login_entries, result.access_entries
+            );
+            Ok(())
+        }
+        Command::Token { command } => {
+            let (configuration, db_conn) = init(&args.config).await?;
+            token(command, &configuration, &db_conn).await
+        }
+    }
+}
 
-    let configuration = Arc::new(Settings::load(&args.config)?);
+/// Loads the configuration, sets up logging and connects to the database
+async fn init(config_paths: &[String]) -> Result<(Arc<Settings>, DatabaseConnection)> {
+    // Logging is part of the configuration, failures to load it are only reported on stderr
+    let configuration = Arc::new(Settings::load(config_paths)?);
+    logging::init(&configuration.logging)?;
+    info!("Initializing mordor version {}", VERSION);
     configuration.sanity_check()?;
-    debug!("Loaded configuration from {}", args.config);
+    debug!("Loaded configuration from {}", config_paths.join(", "));
     trace!("Loaded configuration: {:#?}", configuration);
 
     let db_conn = database::init(&configuration.database).await?;
     info!("Database connection established");
 
-    HttpServer::new(enclose!((db_conn, configuration), move || {
-        let login_modules = {
-            let mut builder = controllers::ModuleBuilder::new();
-            builder.register_module(Box::<BasicAuthLoginModule>::default());
-            builder.register_module(Box::new(SAMLLoginModule::new(&configuration.modules.saml)));
-            builder.build()
-        };
+    Ok((configuration, db_conn))
+}
+
+fn config(command: ConfigCommand, config_paths: &[String]) -> Result<()> {
+    match command {
+        ConfigCommand::Check => {
+            Settings::load(config_paths)
+                .and_then(|configuration| configuration.sanity_check())
+                .wrap_err("Invalid configuration")?;
+            println!("Configuration is valid");
+        }
+        ConfigCommand::PrintDefault => print!("{}", settings::SAMPLE),
+    }
+
+    Ok(())
+}
+
+async fn token(
+    command: TokenCommand,
+    configuration: &Settings,
+    db_conn: &DatabaseConnection,
+) -> Result<()> {
+    match command {
+        TokenCommand::Create {
+            user,
+            name,
+            scopes,
+            expires_days,
+            no_expiry,
+        } => {
+            if let Some(scope) = scopes
+                .iter()
+                .find(|scope| !api_tokens::is_valid_scope(scope))
+            {
+                bail!("Invalid scope '{scope}'");
+            }
+            if expires_days == Some(0) {
+                bail!("Tokens must be valid for at least one day");
+            }
 
-        App::new()
-            .wrap(NormalizePath::new(TrailingSlash::Always))
-            .wrap(Logger::default())
-            .wrap(
-                SessionMiddleware::builder(
-                    session::CookieTTLSessionStore,
-                    Key::from(&configuration.secret_key),
+            let expires_at = (!no_expiry).then(|| {
+                api_tokens::expires_in(
+                    expires_days.unwrap_or(configuration.api_tokens.default_lifetime_days),
                 )
-                .cookie_name(COOKIE_NAME.to_string())
-                .build(),
-            )
-            .app_data(web::Data::new(db_conn.clone()))
-            .app_data(web::Data::new(configuration.clone()))
-            .configure(|sc| controllers::initialize(sc, login_modules))
-    }))
-    .bind(configuration.http.address)?
-    .run()
-    .await?;
+            });
+            let (token, model) =
+                api_tokens::create(db_conn, &user, &name, &scopes, expires_at).await?;
+            info!("Created API token {} for user '{user}'", model.id);
+            println!("{token}");
+        }
+        TokenCommand::List { user } => {
+            for model in api_tokens::list(db_conn, user.as_deref()).await? {
+                let expires_at = model
+                    .expires_at
+                    .map_or_else(|| "never".to_owned(), format_timestamp);
+                println!(
+                    "{}\t{}\t{}\t{}\texpires {expires_at}",
+                    model.id, model.username, model.name, model.scopes
+                );
+            }
+        }
+        TokenCommand::Revoke { id } => {
+            if !api_tokens::revoke(db_conn, id, None).await? {
+                bail!("API token {id} does not exist");
+            }
+            info!("Revoked API token {id}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Resources that outlive configuration reloads, shared by the servers started for each
+/// configuration
+struct Shared {
+    db_conn: DatabaseConnection,
+    access_recorder: Option<AccessRecorder>,
+    listeners: Vec<Listener>,
+    tls_config: Option<ServerConfig>,
+}
+
+async fn serve(
+    config_paths: &[String],
+    mut configuration: Arc<Settings>,
+    db_conn: DatabaseConnection,
+) -> Result<()> {
+    if configuration.retention.is_enabled() {
+        tokio::spawn(database::retention::run(
+            db_conn.clone(),
+            configuration.retention.clone(),
+        ));
+    }
+
+    let (access_recorder, access_recorder_handle) = if configuration.store_access_entries {
+        let (recorder, handle) =
+            AccessRecorder::start(db_conn.clone(), configuration.access_entries.clone());
+        (Some(recorder), Some(handle))
+    } else {
+        (None, None)
+    };
+
+    let listeners = listeners::bind(&configuration.http)?;
+
+    let tls_config = match &configuration.http.tls {
+        Some(tls_settings) => {
+            let resolver = Arc::new(CertificateResolver::load(tls_settings)?);
+            tokio::spawn(tls::watch(
+                resolver.clone(),
+                tls_settings.reload_interval_secs,
+            ));
+            Some(tls::server_config(tls_settings, resolver)?)
+        }
+        None => None,
+    };
+
+    for listener in &listeners {
+        match (listener, &tls_config) {
+            (Listener::Tcp(_), Some(_)) => info!("Listening on {listener} (TLS)"),
+            _ => info!("Listening on {listener}"),
+        }
+    }
+
+    let redirect_server = match &configuration.http.tls {
+        Some(tls_settings) if !tls_settings.redirect_listen.is_empty() => {
+            let https_port = HttpsPort(tls_settings.https_port.unwrap_or_else(|| {
+                listeners
+                    .iter()
+                    .find_map(|listener| match listener {
+                        Listener::Tcp(tcp_listener) => {
+                            tcp_listener.local_addr().ok().map(|addr| addr.port())
+                        }
+                        #[cfg(unix)]
+                        _ => None,
+                    })
+                    .unwrap_or(443)
+            }));
+
+            let mut redirect_server = HttpServer::new(move || {
+                App::new()
+                    .app_data(web::Data::new(https_port))
+                    .default_service(web::to(controllers::https_redirect::redirect))
+            })
+            .workers(1);
+            for address in &tls_settings.redirect_listen {
+                info!("Redirecting {address} to HTTPS port {}", https_port.0);
+                redirect_server = redirect_server.bind(address)?;
+            }
+            let redirect_server = redirect_server.run();
+            Some((redirect_server.handle(), tokio::spawn(redirect_server)))
+        }
+        _ => None,
+    };
+
+    let shared = Shared {
+        db_conn,
+        access_recorder,
+        listeners,
+        tls_config,
+    };
+    let mut reloads = reload::triggers(config_paths, &configuration.reload)?;
+
+    let server = start_server(&configuration, &shared)?;
+    let mut server_handle = server.handle();
+    let mut server_task = tokio::spawn(server);
+    loop {
+        tokio::select! {
+            // Stopped by a signal
+            result = &mut server_task => {
+                result??;
+                break;
+            }
+            Some(()) = reloads.recv() => {
+                let new_configuration = match reload::load(config_paths, &configuration) {
+                    Ok(new_configuration) => Arc::new(new_configuration),
+                    Err(e) => {
+                        error!("Not reloading invalid configuration: {e:#}");
+                        continue;
+                    }
+                };
+
+                match start_server(&new_configuration, &shared) {
+                    Ok(server) => {
+                        // The new server accepts connections on the same sockets, while the old
+                        // one finishes its in-flight requests
+                        let old_handle = std::mem::replace(&mut server_handle, server.handle());
+                        let old_task = std::mem::replace(&mut server_task, tokio::spawn(server));
+                        tokio::spawn(async move {
+                            old_handle.stop(true).await;
+                            let _ = old_task.await;
+                        });
+                        configuration = new_configuration;
+                        info!("Configuration reloaded");
+                    }
+                    Err(e) => error!("Error applying reloaded configuration: {e:#}"),
+                }
+            }
+        }
+    }
+
+    if let Some((redirect_handle, redirect_task)) = redirect_server {
+        redirect_handle.stop(true).await;
+        redirect_task.await??;
+    }
+    drop(shared.listeners);
+
+    if let Some(handle) = access_recorder_handle {
+        info!("Flushing pending access entries");
+        handle.shutdown().await;
+    }
 
     Ok(())
 }
+
+fn session_middleware(
+    configuration: &Settings,
+) -> SessionMiddleware<session::CookieTTLSessionStore> {
+    let settings = &configuration.session;
+    let ttl = Duration::seconds(settings.ttl_secs as i64);
+    let lifecycle: SessionLifecycle = if settings.persistent {
+        PersistentSession::default().session_ttl(ttl).into()
+    } else {
+        BrowserSession::default().state_ttl(ttl).into()
+    };
+
+    SessionMiddleware::builder(
+        session::CookieTTLSessionStore,
+        Key::from(&configuration.secret_key),
+    )
+    .cookie_name(settings.cookie_name())
+    .cookie_domain(settings.domain.clone())
+    .cookie_path(configuration.cookie_path().to_owned())
+    .cookie_secure(settings.secure)
+    .cookie_same_site(settings.same_site.into())
+    .session_lifecycle(lifecycle)
+    .build()
+}
+
+/// Starts a server for the given configuration on the shared listeners
+fn start_server(configuration: &Arc<Settings>, shared: &Shared) -> Result<Server> {
+    let login_modules = Arc::new(controllers::modules::from_settings(&configuration.modules)?);
+
+    let jwt_issuer = match &configuration.jwt {
+        Some(jwt_settings) => Some(Arc::new(JwtIssuer::load(jwt_settings)?)),
+        None => None,
+    };
+
+    let templates = web::Data::new(Templates::load(&configuration.templates)?);
+
+    let db_conn = shared.db_conn.clone();
+    let access_recorder = shared.access_recorder.clone();
+    let configuration = configuration.clone();
+    let mut server = HttpServer::new(enclose!(
+        (
+            db_conn,
+            configuration,
+            access_recorder,
+            jwt_issuer,
+            login_modules,
+            templates
+        ),
+        move || {
+            App::new()
+                .wrap(ErrorHandlers::new().default_handler(controllers::error_pages::render))
+                .wrap(NormalizePath::new(TrailingSlash::Always))
+                .wrap(session_middleware(&configuration))
+                .wrap_fn(controllers::request_id::assign)
+                .wrap(Logger::new(ACCESS_LOG_FORMAT).exclude_regex("/(healthz|readyz)/?$"))
+                .app_data(web::Data::new(db_conn.clone()))
+                .app_data(web::Data::new(configuration.clone()))
+                .app_data(templates.clone())
+                .configure(|sc| {
+                    if let Some(access_recorder) = &access_recorder {
+                        sc.app_data(web::Data::new(access_recorder.clone()));
+                    }
+                    if let Some(jwt_issuer) = &jwt_issuer {
+                        sc.app_data(web::Data::from(jwt_issuer.clone()));
+                    }
+                })
+                .configure(|sc| {
+                    controllers::initialize(sc, &configuration, &login_modules, &templates)
+                })
+        }
+    ))
+    .on_connect(tls::on_connect);
+
+    for listener in &shared.listeners {
+        server = match (listener, &shared.tls_config) {
+            (Listener::Tcp(tcp_listener), Some(tls_config)) => {
+                server.listen_rustls_0_21(tcp_listener.try_clone()?, tls_config.clone())?
+            }
+            (Listener::Tcp(tcp_listener), None) => server.listen(tcp_listener.try_clone()?)?,
+            #[cfg(unix)]
+            (Listener::Unix { listener, .. }, _) => server.listen_uds(listener.try_clone()?)?,
+        };
+    }
+
+    Ok(server.run())
+}
@@ -0,0 +1,319 @@
+use std::{collections::BTreeMap, fs, path::PathBuf, time::Duration};
+
+use color_eyre::Result;
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::settings::{reload, Settings};
+
+/// Keys whose values are never logged
+const SECRET_KEYS: &[&str] = &["secret_key", "database.url", "database.password"];
+
+/// Settings that are only read at startup. Changes are reported, but applied after a restart.
+const RESTART_REQUIRED: &[&str] = &[
+    "secret_key",
+    "secret_key_file",
+    "database",
+    "http.address",
+    "http.listen",
+    "http.unix_socket_mode",
+    "http.tls",
+    "store_access_entries",
+    "access_entries",
+    "retention",
+    "reload",
+    "logging",
+];
+
+/// Returns a channel that receives a message whenever the configuration should be reloaded: on
+/// SIGHUP and, if `watch` is enabled, when one of the configuration files changes
+pub fn triggers(
+    config_paths: &[String],
+    settings: &reload::Settings,
+) -> Result<mpsc::Receiver<()>> {
+    // Triggers arriving while a reload is pending are merged into it
+    let (sender, receiver) = mpsc::channel(1);
+
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangup = signal(SignalKind::hangup())?;
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                info!("SIGHUP received; reloading configuration");
+                let _ = sender.try_send(());
+            }
+        });
+    }
+
+    if settings.watch {
+        for config_path in config_paths {
+            tokio::spawn(watch(
+                resolve_path(config_path),
+                Duration::from_secs(settings.interval_secs),
+                sender.clone(),
+            ));
+        }
+    }
+
+    Ok(receiver)
+}
+
+/// Loads and validates the configuration again, logging what changed compared to `current`
+pub fn load(config_paths: &[String], current: &Settings) -> Result<Settings> {
+    let mut new = Settings::load(config_paths)?;
+    new.sanity_check()?;
+
+    let changes = diff(current, &new)?;
+    if changes.is_empty() {
+        info!("Configuration unchanged");
+    }
+    for (key, old_value, new_value) in &changes {
+        if SECRET_KEYS.contains(&key.as_str()) {
+            info!("Configuration changed: {key}");
+        } else {
+            info!("Configuration changed: {key}: {old_value} -> {new_value}");
+        }
+        if requires_restart(key) {
+            warn!("Changes to {key} take effect after a restart");
+        }
+    }
+
+    // Sessions are signed with the key, a new one would log everyone out
+    new.secret_key = current.secret_key.clone();
+    new.secret_key_file = current.secret_key_file.clone();
+
+    Ok(new)
+}
+
+/// Whether `key`, as returned by `diff`, is one of or nested in the `RESTART_REQUIRED` settings
+fn requires_restart(key: &str) -> bool {
+    RESTART_REQUIRED
+        .iter()
+        .any(|prefix| key == *prefix || key.starts_with(&format!("{prefix}.")))
+}
+
+async fn watch(path: PathBuf, interval: Duration, sender: mpsc::Sender<()>) {
+    let modified = |path: &PathBuf| fs::metadata(path).and_then(|metadata| metadata.modified());
+
+    let mut last_modified = match modified(&path) {
+        Ok(last_modified) => last_modified,
+        Err(e) => {
+            warn!("Not watching {} for changes: {e}", path.display());
+            return;
+        }
+    };
+    debug!("Watching {} for changes", path.display());
+
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+
+        match modified(&path) {
+            Ok(current) if current != last_modified => {
+                last_modified = current;
+                info!("{} changed; reloading configuration", path.display());
+                let _ = sender.try_send(());
+            }
+            Ok(_) => {}
+            // The file may be replaced right now, try again on the next tick
+            Err(e) => debug!("Could not check {} for changes: {e}", path.display()),
+        }
+    }
+}
+
+/// Like `config::File::with_name`, which the configuration is loaded with, accepts the path
+/// without its extension
+fn resolve_path(config_path: &str) -> PathBuf {
+    let path = PathBuf::from(config_path);
+    if path.is_file() {
+        return path;
+    }
+
+    ["toml", "json", "yaml", "yml", "ini", "ron", "json5"]
+        .iter()
+        .map(|extension| path.with_extension(extension))
+        .find(|candidate| candidate.is_file())
+        .unwrap_or(path)
+}
+
+/// Returns the keys whose values differ, along with the old and new values
+fn diff(old: &Settings, new: &Settings) -> Result<Vec<(String, Value, Value)>> {
+    let old = flatten(serde_json::to_value(old)?);
+    let new = flatten(serde_json::to_value(new)?);
+
+    let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    Ok(keys
+        .into_iter()
+        .filter_map(|key| {
+            let old_value = old.get(key).cloned().unwrap_or(Value::Null);
+            let new_value = new.get(key).cloned().unwrap_or(Value::Null);
+            (old_value != new_value).then(|| (key.clone(), old_value, new_value))
+        })
+        .collect())
+}
+
+/// Flattens nested objects and arrays of tables into dotted keys, e.g. `modules.saml.0.name`.
+/// Other arrays are compared as a whole, empty ones are left out like unset values.
+fn flatten(value: Value) -> BTreeMap<String, Value> {
+    fn visit(prefix: String, value: Value, flat: &mut BTreeMap<String, Value>) {
+        let join = |key: String| {
+            if prefix.is_empty() {
+                key
+            } else {
+                format!("{prefix}.{key}")
+            }
+        };
+
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    visit(join(key), value, flat);
+                }
+            }
+            Value::Array(values) if values.is_empty() => {}
+            Value::Array(values) if values.iter().all(Value::is_object) => {
+                for (index, value) in values.into_iter().enumerate() {
+                    visit(join(index.to_string()), value, flat);
+                }
+            }
+            value => {
+                flat.insert(prefix, value);
+            }
+        }
+    }
+
+    let mut flat = BTreeMap::new();
+    visit(String::new(), value, &mut flat);
+    flat
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(name: &str, contents: &str) -> Settings {
+        let path = std::env::temp_dir().join(format!(
+            "mordor-test-{}-reload-{name}.toml",
+            std::process::id()
+        ));
+        fs::write(&path, format!("[database]\n[http]\n{contents}")).unwrap();
+        let settings = Settings::load(&[path.to_string_lossy().into_owned()]).unwrap();
+        fs::remove_file(path).unwrap();
+        settings
+    }
+
+    #[test]
+    fn startup_settings_require_restart() {
+        for key in [
+            "database.url",
+            "http.address",
+            "http.listen",
+            "http.tls.cert_file",
+            "logging.level",
+            "logging.file.directory",
+            "reload.watch",
+            "secret_key",
+            "secret_key_file",
+        ] {
+            assert!(requires_restart(key), "{key}");
+        }
+    }
+
+    #[test]
+    fn other_settings_are_reloadable() {
+        for key in [
+            "session.ttl_secs",
+            "http.path",
+            "http.auth_mode",
+            "http.address_family",
+            "modules.saml.0.name",
+            "databases",
+            "secret_key_files",
+        ] {
+            assert!(!requires_restart(key), "{key}");
+        }
+    }
+
+    #[test]
+    fn unchanged_settings() {
+        let old = settings("unchanged-old", "");
+        let new = settings("unchanged-new", "");
+        assert!(diff(&old, &new).unwrap().is_empty());
+    }
+
+    #[test]
+    fn changed_settings() {
+        let old = settings("changed-old", "");
+        let new = settings(
+            "changed-new",
+            "listen = [\"unix:/run/mordor.sock\"]\npath = \"/mordor\"\n",
+        );
+        let changes = diff(&old, &new).unwrap();
+        let keys: Vec<&str> = changes.iter().map(|(key, _, _)| key.as_str()).collect();
+        assert_eq!(keys, ["http.listen", "http.path"]);
+        assert_eq!(changes[0].1, Value::Null);
+        assert_eq!(changes[0].2, serde_json::json!(["unix:/run/mordor.sock"]));
+        assert!(requires_restart(keys[0]));
+        assert!(!requires_restart(keys[1]));
+    }
+
+    #[test]
+    fn changed_module_settings() {
+        let modules = |name: &str| {
+            format!(
+                "[[modules.saml]]\nupstream_url = \"http://127.0.0.1:9/\"\n\
+                 [[modules.saml]]\nupstream_url = \"http://127.0.0.1:10/\"\nname = \"{name}\"\n"
+            )
+        };
+        let old = settings("module-old", &modules("a"));
+        let new = settings("module-new", &modules("b"));
+        let changes = diff(&old, &new).unwrap();
+        assert_eq!(
+            changes,
+            [(
+                "modules.saml.1.name".to_string(),
+                Value::from("a"),
+                Value::from("b")
+            )]
+        );
+        assert!(!requires_restart(&changes[0].0));
+    }
+
+    #[test]
+    fn secret_key_is_kept() {
+        let path = |name: &str| {
+            std::env::temp_dir().join(format!(
+                "mordor-test-{}-reload-{name}.toml",
+                std::process::id()
+            ))
+        };
+        let key_file = path("secret-key");
+        let config_file = path("secret-key-config");
+        let config_paths = [config_file.to_string_lossy().into_owned()];
+        fs::write(
+            &config_file,
+            format!(
+                "secret_key_file = {:?}\n[database]\n[http]\n",
+                key_file.display()
+            ),
+        )
+        .unwrap();
+
+        fs::write(&key_file, "ab".repeat(64)).unwrap();
+        let current = Settings::load(&config_paths).unwrap();
+        fs::write(&key_file, "cd".repeat(64)).unwrap();
+        let new = load(&config_paths, &current).unwrap();
+        fs::remove_file(key_file).unwrap();
+        fs::remove_file(config_file).unwrap();
+
+        assert_eq!(new.secret_key, vec![0xab; 64]);
+        assert_eq!(new.secret_key, current.secret_key);
+    }
+}
@@ -58,10 +58,10 @@ impl SessionStore for CookieTTLSessionStore {
             .map_err(anyhow::Error::new)
             .map_err(SaveError::Serialization)?;
 
-        Ok(session_key
+        session_key
             .try_into()
             .map_err(Into::into)
-            .map_err(SaveError::Other)?)
+            .map_err(SaveError::Other)
     }
 
     async fn update(
@@ -78,11 +78,7 @@ impl SessionStore for CookieTTLSessionStore {
         session_key: &SessionKey,
         ttl: &Duration,
     ) -> Result<(), anyhow::Error> {
-        let state = if let Some(state) = self.load(session_key).await? {
-            state
-        } else {
-            HashMap::new()
-        };
+        let state = self.load(session_key).await?.unwrap_or_default();
 
         self.update_simple(state, ttl).await?;
         Ok(())
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+use crate::utils::defaults;
+
+/// Tuning of how access entries are recorded, when `store_access_entries` is enabled
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Settings {
+    /// Maximum number of entries inserted in a single statement
+    #[serde(default = "defaults::access_entries::batch_size")]
+    pub batch_size: usize,
+    /// Maximum time an entry waits in memory before being written, in milliseconds
+    #[serde(default = "defaults::access_entries::flush_interval_ms")]
+    pub flush_interval_ms: u64,
+    /// Maximum number of entries waiting to be written; further entries are dropped
+    #[serde(default = "defaults::access_entries::queue_size")]
+    pub queue_size: usize,
+    /// Repeated accesses by the same user within this many seconds are recorded only once
+    #[serde(default)]
+    pub dedup_window_secs: Option<u64>,
+    /// Store the host of the accessed site
+    #[serde(default = "defaults::access_entries::record_field")]
+    pub record_host: bool,
+    /// Store the requested URI
+    #[serde(default = "defaults::access_entries::record_field")]
+    pub record_uri: bool,
+    /// Store the HTTP method of the request
+    #[serde(default = "defaults::access_entries::record_field")]
+    pub record_method: bool,
+    /// Store the client's IP address
+    #[serde(default = "defaults::access_entries::record_field")]
+    pub record_client_ip: bool,
+    /// Store the client's user agent
+    #[serde(default = "defaults::access_entries::record_field")]
+    pub record_user_agent: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            batch_size: defaults::access_entries::batch_size(),
+            flush_interval_ms: defaults::access_entries::flush_interval_ms(),
+            queue_size: defaults::access_entries::queue_size(),
+            dedup_window_secs: None,
+            record_host: defaults::access_entries::record_field(),
+            record_uri: defaults::access_entries::record_field(),
+            record_method: defaults::access_entries::record_field(),
+            record_client_ip: defaults::access_entries::record_field(),
+            record_user_agent: defaults::access_entries::record_field(),
+        }
+    }
+}
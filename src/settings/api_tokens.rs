@@ -0,0 +1,46 @@
+use color_eyre::{eyre::bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::defaults;
+
+/// Personal API tokens, accepted by `/auth/` as `Authorization: Bearer` tokens
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Settings {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Let logged in users create and revoke their own tokens under `/tokens/`
+    #[serde(default = "defaults::api_tokens::self_service")]
+    pub self_service: bool,
+    /// Lifetime of new tokens unless specified otherwise, in days
+    #[serde(default = "defaults::api_tokens::default_lifetime_days")]
+    pub default_lifetime_days: u32,
+    /// Longest lifetime users can choose on the self-service page, in days
+    #[serde(default)]
+    pub max_lifetime_days: Option<u32>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            self_service: defaults::api_tokens::self_service(),
+            default_lifetime_days: defaults::api_tokens::default_lifetime_days(),
+            max_lifetime_days: None,
+        }
+    }
+}
+
+impl Settings {
+    pub fn sanity_check(&self) -> Result<()> {
+        if self.default_lifetime_days == 0 || self.max_lifetime_days == Some(0) {
+            bail!("API token lifetimes must be greater than zero");
+        }
+        if let Some(max_lifetime_days) = self.max_lifetime_days {
+            if self.default_lifetime_days > max_lifetime_days {
+                bail!("API token default_lifetime_days must not exceed max_lifetime_days");
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,13 +1,256 @@
-use std::net::SocketAddrV4;
+use std::{fmt, net::SocketAddr, path::PathBuf, str::FromStr};
 
-use serde::Deserialize;
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use serde::{Deserialize, Serialize, Serializer};
+use url::Url;
 
-use crate::utils::defaults;
+use crate::{settings::tls, utils::defaults};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Settings {
+    /// Single TCP address to listen on, used when `listen` is empty
     #[serde(default = "defaults::http::address")]
-    pub address: SocketAddrV4,
+    pub address: SocketAddr,
+    /// Addresses to listen on: `127.0.0.1:8080`, `[::]:8080` or `unix:/run/mordor.sock`
+    #[serde(default)]
+    pub listen: Vec<ListenAddress>,
+    /// Permissions of Unix domain sockets as an octal string, e.g. `"660"`
+    #[serde(default)]
+    pub unix_socket_mode: Option<String>,
+    /// Prefix under which mordor is served, e.g. `/mordor`
     #[serde(default)]
     pub path: Option<String>,
+    /// Terminate TLS on the TCP listeners
+    #[serde(default)]
+    pub tls: Option<tls::Settings>,
+    /// How `/auth/` responds to unauthenticated requests
+    #[serde(default)]
+    pub auth_mode: AuthMode,
+    /// URL at which browsers reach mordor, e.g. `https://auth.example.com`. Used for redirects to
+    /// the login page in `forward_auth` mode; without it, they are relative to the protected host.
+    #[serde(default)]
+    pub public_url: Option<Url>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMode {
+    /// Always respond with 401 and let the proxy redirect, as with nginx `auth_request`
+    #[default]
+    AuthRequest,
+    /// Redirect browsers to the login page and respond with 401 to other clients, as Traefik
+    /// ForwardAuth and Caddy `forward_auth` expect
+    ForwardAuth,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ListenAddress {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl FromStr for ListenAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err("Unix socket path must not be empty".to_string());
+            }
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+
+        s.parse()
+            .map(Self::Tcp)
+            .map_err(|e| format!("Invalid listen address '{s}': {e}"))
+    }
+}
+
+impl Serialize for ListenAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl TryFrom<String> for ListenAddress {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for ListenAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "{addr}"),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+impl Settings {
+    /// All addresses to listen on
+    pub fn listen_addresses(&self) -> Vec<ListenAddress> {
+        if self.listen.is_empty() {
+            vec![ListenAddress::Tcp(self.address)]
+        } else {
+            self.listen.clone()
+        }
+    }
+
+    pub fn unix_socket_mode(&self) -> Result<Option<u32>> {
+        self.unix_socket_mode
+            .as_deref()
+            .map(|mode| {
+                u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+                    .ok()
+                    .filter(|mode| *mode <= 0o777)
+                    .ok_or_else(|| {
+                        eyre!("Invalid Unix socket mode '{mode}', expected e.g. \"660\"")
+                    })
+            })
+            .transpose()
+    }
+
+    /// Mount prefix without a trailing slash; empty when served from the root
+    pub fn base_path(&self) -> &str {
+        match self.path.as_deref() {
+            None | Some("/") => "",
+            Some(path) => path,
+        }
+    }
+
+    /// Default path of the session cookie, covering everything mordor serves
+    pub fn cookie_path(&self) -> &str {
+        match self.base_path() {
+            "" => "/",
+            path => path,
+        }
+    }
+
+    /// URL of the login page, absolute if `public_url` is set
+    pub fn login_url(&self) -> String {
+        let origin = self
+            .public_url
+            .as_ref()
+            .map_or("", |public_url| public_url.as_str().trim_end_matches('/'));
+        format!("{origin}{}/login/", self.base_path())
+    }
+
+    pub fn sanity_check(&self) -> Result<()> {
+        let path = self.base_path();
+        if !path.is_empty() && (!path.starts_with('/') || path.ends_with('/')) {
+            bail!("HTTP path '{path}' must start with '/' and must not end with '/'");
+        }
+
+        if let Some(public_url) = &self.public_url {
+            if !matches!(public_url.scheme(), "http" | "https")
+                || public_url.query().is_some()
+                || public_url.fragment().is_some()
+            {
+                bail!("HTTP public_url '{public_url}' must be an http(s) URL without query");
+            }
+        }
+
+        let addresses = self.listen_addresses();
+        for (i, address) in addresses.iter().enumerate() {
+            if addresses[..i].contains(address) {
+                bail!("Listen address '{address}' is configured more than once");
+            }
+            if matches!(address, ListenAddress::Unix(_)) && cfg!(not(unix)) {
+                bail!("Unix domain sockets are not supported on this platform");
+            }
+        }
+        self.unix_socket_mode()?;
+
+        if let Some(tls) = &self.tls {
+            tls.sanity_check()?;
+            if !addresses
+                .iter()
+                .any(|address| matches!(address, ListenAddress::Tcp(_)))
+            {
+                bail!("TLS is configured, but there are no TCP listeners to use it");
+            }
+            for address in &tls.redirect_listen {
+                if addresses.contains(&ListenAddress::Tcp(*address)) {
+                    bail!("Redirect address '{address}' is also configured as a TLS listener");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_mode(mode: &str) -> Settings {
+        serde_json::from_value(serde_json::json!({ "unix_socket_mode": mode })).unwrap()
+    }
+
+    #[test]
+    fn tcp_addresses() {
+        assert_eq!(
+            "127.0.0.1:8080".parse::<ListenAddress>(),
+            Ok(ListenAddress::Tcp(([127, 0, 0, 1], 8080).into()))
+        );
+        assert_eq!(
+            "[::1]:8080".parse::<ListenAddress>(),
+            Ok(ListenAddress::Tcp("[::1]:8080".parse().unwrap()))
+        );
+    }
+
+    #[test]
+    fn unix_addresses() {
+        assert_eq!(
+            "unix:/run/mordor.sock".parse::<ListenAddress>(),
+            Ok(ListenAddress::Unix(PathBuf::from("/run/mordor.sock")))
+        );
+        assert_eq!(
+            "unix:".parse::<ListenAddress>(),
+            Err("Unix socket path must not be empty".to_string())
+        );
+    }
+
+    #[test]
+    fn invalid_addresses() {
+        for address in [
+            "",
+            "localhost:8080",
+            "127.0.0.1",
+            "::1:8080",
+            "[::1]",
+            "unix",
+        ] {
+            let error = address.parse::<ListenAddress>().unwrap_err();
+            assert!(
+                error.starts_with(&format!("Invalid listen address '{address}'")),
+                "{error}"
+            );
+        }
+    }
+
+    #[test]
+    fn addresses_round_trip() {
+        for address in ["127.0.0.1:8080", "[::1]:8080", "unix:/run/mordor.sock"] {
+            let parsed: ListenAddress = address.parse().unwrap();
+            assert_eq!(parsed.to_string(), address);
+        }
+    }
+
+    #[test]
+    fn socket_modes() {
+        assert_eq!(with_mode("660").unix_socket_mode().unwrap(), Some(0o660));
+        assert_eq!(with_mode("0o600").unix_socket_mode().unwrap(), Some(0o600));
+        for mode in ["", "rw", "689", "1000"] {
+            assert!(with_mode(mode).unix_socket_mode().is_err(), "{mode}");
+        }
+    }
 }
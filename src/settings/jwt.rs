@@ -0,0 +1,52 @@
+use std::{path::PathBuf, str::FromStr};
+
+use actix_web::http::header::HeaderName;
+use color_eyre::{eyre::bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::defaults;
+
+/// Optional claims of issued JWTs. `sub`, `iat` and `exp` are always included.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Claim {
+    /// Groups of the login module the user logged in through
+    Groups,
+    /// Name of the login module the user logged in through, or `api_token`
+    Module,
+}
+
+/// JWTs issued on successful `/auth/` checks, signed with Ed25519
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Settings {
+    /// PEM file with the PKCS#8 encoded Ed25519 private key, e.g. generated with
+    /// `openssl genpkey -algorithm ed25519`
+    pub key_file: PathBuf,
+    /// Response header the JWT is returned in
+    #[serde(default = "defaults::jwt::header")]
+    pub header: String,
+    /// Lifetime of issued JWTs, in seconds
+    #[serde(default = "defaults::jwt::ttl_secs")]
+    pub ttl_secs: u64,
+    /// Value of the `iss` claim
+    #[serde(default)]
+    pub issuer: Option<String>,
+    /// Value of the `aud` claim
+    #[serde(default)]
+    pub audience: Option<String>,
+    #[serde(default = "defaults::jwt::claims")]
+    pub claims: Vec<Claim>,
+}
+
+impl Settings {
+    pub fn sanity_check(&self) -> Result<()> {
+        if self.ttl_secs == 0 {
+            bail!("JWT ttl_secs must be greater than zero");
+        }
+        if HeaderName::from_str(&self.header).is_err() {
+            bail!("JWT header '{}' is not a valid header name", self.header);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::{filter::LevelFilter, EnvFilter};
+
+use crate::utils::defaults;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// Human readable lines, coloured when written to a terminal
+    #[default]
+    Text,
+    /// One JSON object per event, including the fields of the spans it happened in
+    Json,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Rotation {
+    Hourly,
+    #[default]
+    Daily,
+    Never,
+}
+
+/// Log file, instead of standard error
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct File {
+    pub directory: PathBuf,
+    /// Name of the log file, followed by the date and hour when rotated
+    #[serde(default = "defaults::logging::file_name")]
+    pub file_name: String,
+    #[serde(default)]
+    pub rotation: Rotation,
+    /// Number of rotated files to keep, all are kept if not set
+    #[serde(default)]
+    pub max_files: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Settings {
+    /// Level of events that are logged: `error`, `warn`, `info`, `debug` or `trace`. The
+    /// `RUST_LOG` environment variable overrides this and `modules`.
+    #[serde(default = "defaults::logging::level")]
+    pub level: String,
+    /// Levels of specific modules, e.g. `sea_orm = "warn"` or `"mordor::controllers" = "debug"`
+    #[serde(default)]
+    pub modules: BTreeMap<String, String>,
+    #[serde(default)]
+    pub format: Format,
+    #[serde(default)]
+    pub file: Option<File>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            level: defaults::logging::level(),
+            modules: BTreeMap::new(),
+            format: Format::default(),
+            file: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Filter selecting the events to log
+    pub fn filter(&self) -> Result<EnvFilter> {
+        match EnvFilter::try_from_default_env() {
+            Ok(filter) => Ok(filter),
+            Err(_) => self.configured_filter(),
+        }
+    }
+
+    fn configured_filter(&self) -> Result<EnvFilter> {
+        // Workers log every connection that fails, e.g. on TLS handshake errors
+        let mut directives = vec![
+            level(&self.level)?.to_string(),
+            "actix_server::worker=warn".to_owned(),
+        ];
+        for (module, module_level) in &self.modules {
+            if module.is_empty() || module.contains([',', '=', '[', ' ']) {
+                bail!("Logging module '{module}' is not a module path");
+            }
+            directives.push(format!("{module}={}", level(module_level)?));
+        }
+        EnvFilter::try_new(directives.join(",")).wrap_err("Invalid logging levels")
+    }
+
+    pub fn sanity_check(&self) -> Result<()> {
+        self.configured_filter()?;
+        Ok(())
+    }
+}
+
+/// Levels are parsed on their own, as a filter would take a misspelled level for a module name
+fn level(level: &str) -> Result<LevelFilter> {
+    level.parse().wrap_err_with(|| {
+        format!("Logging level '{level}' must be one of off, error, warn, info, debug or trace")
+    })
+}
@@ -1,38 +1,390 @@
-use color_eyre::{eyre::bail, Result};
-use config::{Config, ConfigError, Environment, File};
-use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use crate::utils::defaults;
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use config::{Config, Environment, File};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
+use crate::{
+    controllers,
+    jwt::JwtIssuer,
+    settings::tls::ClientAuth,
+    templates::Templates,
+    tls::{self as tls_server, CertificateResolver},
+    utils::defaults,
+};
+
+pub mod access_entries;
+pub mod api_tokens;
 pub mod database;
 pub mod http;
+pub mod jwt;
+pub mod logging;
 pub mod modules;
+pub mod reload;
+pub mod retention;
+pub mod session;
+pub mod templates;
+pub mod tls;
+
+/// Commented sample configuration, printed by `mordor config print-default`
+pub const SAMPLE: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/src/_static/config.sample.toml"
+));
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Settings {
     pub database: database::Settings,
     pub http: http::Settings,
-    #[serde(with = "hex")]
+    /// Hex encoded key for signing session cookies
+    #[serde(with = "hex", default)]
     pub secret_key: Vec<u8>,
+    /// File containing the hex encoded `secret_key`, e.g. a mounted Docker secret
+    #[serde(default)]
+    pub secret_key_file: Option<PathBuf>,
     #[serde(default = "defaults::store_access_entries")]
     pub store_access_entries: bool,
+    #[serde(default)]
+    pub access_entries: access_entries::Settings,
+    #[serde(default)]
     pub modules: modules::Settings,
+    #[serde(default)]
+    pub retention: retention::Settings,
+    #[serde(default)]
+    pub api_tokens: api_tokens::Settings,
+    #[serde(default)]
+    pub jwt: Option<jwt::Settings>,
+    #[serde(default)]
+    pub reload: reload::Settings,
+    #[serde(default)]
+    pub session: session::Settings,
+    #[serde(default)]
+    pub templates: templates::Settings,
+    #[serde(default)]
+    pub logging: logging::Settings,
 }
 
 impl Settings {
-    pub fn load(file_path: &str) -> Result<Self, ConfigError> {
-        Config::builder()
-            .add_source(File::with_name(file_path))
-            .add_source(Environment::default().keep_prefix(false).prefix("MORDOR"))
+    /// Loads the configuration files in order, each overriding the ones before it, followed by
+    /// `MORDOR_*` environment variables. Nested keys are separated by `__` in variable names,
+    /// e.g. `MORDOR_DATABASE__PASSWORD_FILE`.
+    pub fn load(file_paths: &[String]) -> Result<Self> {
+        let mut files = Config::builder();
+        for file_path in file_paths {
+            files = files.add_source(File::with_name(file_path));
+        }
+        let files = files.build()?;
+
+        let mut settings: Self = Config::builder()
+            .add_source(files.clone())
+            .add_source(
+                Environment::default()
+                    .keep_prefix(false)
+                    .prefix("MORDOR")
+                    .prefix_separator("_")
+                    .separator("__"),
+            )
             .build()?
-            .try_deserialize()
+            .try_deserialize()?;
+
+        // Environment variables aren't checked, other software may use the same prefix
+        let mut unknown = vec![];
+        unknown_keys(
+            "",
+            &files.try_deserialize()?,
+            &serde_json::to_value(&settings)?,
+            &mut unknown,
+        );
+        if !unknown.is_empty() {
+            bail!("Unknown configuration settings: {}", unknown.join(", "));
+        }
+
+        settings.read_secret_files()?;
+        Ok(settings)
+    }
+
+    /// Replaces secrets configured through `*_file` settings with the contents of those files
+    fn read_secret_files(&mut self) -> Result<()> {
+        if let Some(path) = &self.secret_key_file {
+            if !self.secret_key.is_empty() {
+                bail!("Only one of secret_key and secret_key_file may be set");
+            }
+            self.secret_key = hex::decode(read_secret(path)?).wrap_err_with(|| {
+                format!("{} does not contain a hex encoded key", path.display())
+            })?;
+        }
+
+        self.database.read_secret_files()
+    }
+
+    /// Path of the session cookie, covering everything mordor serves unless configured otherwise
+    pub fn cookie_path(&self) -> &str {
+        self.session
+            .path
+            .as_deref()
+            .unwrap_or_else(|| self.http.cookie_path())
     }
 
     pub fn sanity_check(&self) -> Result<()> {
-        if self.secret_key.len() < 32 {
-            bail!("Secret key must be at least 32 bytes long");
+        if self.secret_key.is_empty() {
+            bail!("Either secret_key or secret_key_file must be set");
+        }
+        if self.secret_key.len() < 64 {
+            bail!("Secret key must be at least 64 bytes long");
+        }
+
+        self.database.sanity_check()?;
+        self.http.sanity_check()?;
+        self.session.sanity_check()?;
+        self.logging.sanity_check()?;
+        self.check_cookie()?;
+        if let Some(tls) = &self.http.tls {
+            let resolver = CertificateResolver::load(tls)?;
+            tls_server::server_config(tls, Arc::new(resolver))?;
+        }
+
+        for saml in &self.modules.saml {
+            saml.sanity_check()?;
+        }
+        let client_auth = self
+            .http
+            .tls
+            .as_ref()
+            .map_or(ClientAuth::None, |tls| tls.client_auth);
+        if client_auth == ClientAuth::None
+            && self
+                .modules
+                .client_cert
+                .iter()
+                .any(|module| module.common.enabled && module.header.is_none())
+        {
+            bail!("Client certificate login without a `header` requires `http.tls.client_auth`");
+        }
+        // Also checks that names and subpaths are unique
+        controllers::modules::from_settings(&self.modules)?;
+        Templates::load(&self.templates)?;
+
+        if self.access_entries.batch_size == 0 || self.access_entries.queue_size == 0 {
+            bail!("Access entry batch_size and queue_size must be greater than zero");
+        }
+        if self.access_entries.flush_interval_ms == 0 {
+            bail!("Access entry flush interval must be greater than zero");
+        }
+
+        if self.retention.is_enabled() && self.retention.interval_secs == 0 {
+            bail!("Retention interval must be greater than zero");
+        }
+        if self.retention.login_entries.max_rows == Some(0)
+            || self.retention.access_entries.max_rows == Some(0)
+        {
+            bail!("Retention max_rows must be greater than zero");
+        }
+
+        if self.reload.watch && self.reload.interval_secs == 0 {
+            bail!("Reload interval must be greater than zero");
+        }
+
+        self.api_tokens.sanity_check()?;
+        if let Some(jwt) = &self.jwt {
+            jwt.sanity_check()?;
+            JwtIssuer::load(jwt)?;
         }
 
         Ok(())
     }
+
+    /// Checks that browsers will send the session cookie to mordor
+    fn check_cookie(&self) -> Result<()> {
+        let path = self.cookie_path();
+        let base_path = self.http.base_path();
+        let prefix = path.trim_end_matches('/');
+        if base_path != prefix && !base_path.starts_with(&format!("{prefix}/")) {
+            bail!("Session cookie path '{path}' must include the HTTP path '{base_path}'");
+        }
+        if self.session.host_prefix && path != "/" {
+            bail!("Session cookies with host_prefix must have path '/'");
+        }
+
+        if let Some(public_url) = &self.http.public_url {
+            let host = public_url.host_str().unwrap_or_default();
+            // Browsers only treat plain HTTP on loopback as secure
+            let loopback = matches!(host, "localhost" | "127.0.0.1" | "[::1]");
+            if self.session.secure && public_url.scheme() == "http" && !loopback {
+                bail!(
+                    "HTTP public_url '{public_url}' must use https, as session cookies are secure"
+                );
+            }
+
+            if let Some(domain) = &self.session.domain {
+                let domain = domain.trim_start_matches('.');
+                if host != domain && !host.ends_with(&format!(".{domain}")) {
+                    bail!("Session cookie domain '{domain}' does not include public_url host '{host}'");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads a secret from a file, without the trailing newline most editors and `echo` add
+pub fn read_secret(path: &Path) -> Result<String> {
+    let secret = fs::read_to_string(path)
+        .wrap_err_with(|| format!("Could not read secret from {}", path.display()))?;
+    Ok(secret.trim_end_matches(['\r', '\n']).to_owned())
+}
+
+/// Collects the keys in `raw`, the configuration as written, that don't appear in `known`, the
+/// settings they were deserialized into
+fn unknown_keys(prefix: &str, raw: &Value, known: &Value, unknown: &mut Vec<String>) {
+    let join = |key: &str| {
+        if prefix.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{prefix}.{key}")
+        }
+    };
+
+    match (raw, known) {
+        (Value::Object(raw), Value::Object(known)) => {
+            for (key, value) in raw {
+                match known.get(key) {
+                    Some(known_value) => unknown_keys(&join(key), value, known_value, unknown),
+                    None => unknown.push(join(key)),
+                }
+            }
+        }
+        (Value::Array(raw), Value::Array(known)) => {
+            for (index, (value, known_value)) in raw.iter().zip(known).enumerate() {
+                unknown_keys(&join(&index.to_string()), value, known_value, unknown);
+            }
+        }
+        // A module configured as a single table rather than an array of tables
+        (Value::Object(_), Value::Array(known)) => {
+            if let Some(known_value) = known.first() {
+                unknown_keys(prefix, raw, known_value, unknown);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a configuration file for `Settings::load`, which only reads files
+    fn config_file(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "mordor-test-{}-settings-{name}.toml",
+            std::process::id()
+        ));
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    /// Loads `contents` on top of a file with the required `[database]` and `[http]` tables
+    fn load_error(name: &str, contents: &str) -> String {
+        let base = config_file(&format!("{name}-base"), "[database]\n[http]\n");
+        let path = config_file(name, contents);
+        let error = Settings::load(&[base.clone(), path.clone()])
+            .unwrap_err()
+            .to_string();
+        std::fs::remove_file(base).unwrap();
+        std::fs::remove_file(path).unwrap();
+        error
+    }
+
+    #[test]
+    fn known_keys_are_accepted() {
+        let path = config_file(
+            "known",
+            r#"
+            [database]
+            file = "mordor.db"
+            [http]
+            listen = ["127.0.0.1:8080", "unix:/run/mordor.sock"]
+            [[modules.saml]]
+            upstream_url = "http://127.0.0.1:8000"
+            [[modules.saml]]
+            name = "other"
+            subpath = "/other"
+            upstream_url = "http://127.0.0.1:8001"
+            [modules.basic]
+            display_names = { nl = "Externe gebruikers" }
+            [logging.modules]
+            sea_orm = "warn"
+            "#,
+        );
+        let settings = Settings::load(std::slice::from_ref(&path)).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(settings.modules.saml.len(), 2);
+        assert_eq!(settings.modules.basic.len(), 1);
+    }
+
+    #[test]
+    fn unknown_top_level_key() {
+        let error = load_error("top", "bogus = 1\n");
+        assert_eq!(error, "Unknown configuration settings: bogus");
+    }
+
+    #[test]
+    fn unknown_nested_key() {
+        let error = load_error("nested", "[http]\nbogus = 1\n");
+        assert_eq!(error, "Unknown configuration settings: http.bogus");
+    }
+
+    #[test]
+    fn unknown_key_in_module_array() {
+        let error = load_error(
+            "array",
+            r#"
+            [[modules.saml]]
+            upstream_url = "http://127.0.0.1:8000"
+            [[modules.saml]]
+            name = "other"
+            subpath = "/other"
+            upstream_url = "http://127.0.0.1:8001"
+            bogus = 1
+            "#,
+        );
+        assert_eq!(
+            error,
+            "Unknown configuration settings: modules.saml.1.bogus"
+        );
+    }
+
+    #[test]
+    fn unknown_key_in_single_module() {
+        let error = load_error(
+            "single",
+            r#"
+            [modules.saml]
+            upstream_url = "http://127.0.0.1:8000"
+            bogus = 1
+            "#,
+        );
+        assert_eq!(error, "Unknown configuration settings: modules.saml.bogus");
+    }
+
+    #[test]
+    fn all_unknown_keys_are_reported() {
+        let error = load_error(
+            "several",
+            "[http]\nbogus = 1\n[session]\nsame_sight = \"lax\"\n",
+        );
+        assert_eq!(
+            error,
+            "Unknown configuration settings: http.bogus, session.same_sight"
+        );
+    }
 }
@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+use super::Common;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Settings {
+    #[serde(flatten)]
+    pub common: Common,
+}
@@ -0,0 +1,63 @@
+use std::net::IpAddr;
+
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::Common;
+use crate::utils::defaults;
+
+/// Part of a client certificate that a username can be derived from
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CertificateField {
+    /// Common name (CN) of the subject
+    CommonName,
+    /// E-mail address in the subject alternative names
+    Email,
+    /// DNS name in the subject alternative names
+    Dns,
+}
+
+/// Maps a certificate field to a username. Rules are tried in order, the first match wins.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Rule {
+    pub field: CertificateField,
+    /// Regular expression the field has to match
+    #[serde(
+        deserialize_with = "deserialize_regex",
+        serialize_with = "serialize_regex"
+    )]
+    pub pattern: Regex,
+    /// Username, may refer to capture groups of `pattern` (e.g. `$1` or `${user}`)
+    #[serde(default = "defaults::modules::client_cert_username")]
+    pub username: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Settings {
+    #[serde(flatten)]
+    pub common: Common,
+    /// Header in which the proxy forwards the (URL-encoded) PEM client certificate, e.g.
+    /// `X-SSL-Client-Cert`. Certificates presented to mordor's own TLS listeners are always used.
+    #[serde(default)]
+    pub header: Option<String>,
+    /// Header in which the proxy forwards the result of verifying the certificate, which has to
+    /// be `SUCCESS`
+    #[serde(default)]
+    pub verify_header: Option<String>,
+    /// Peer addresses allowed to forward certificates in `header`, in addition to connections
+    /// over Unix domain sockets
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
+    #[serde(default = "defaults::modules::client_cert_rules")]
+    pub rules: Vec<Rule>,
+}
+
+fn deserialize_regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Regex, D::Error> {
+    let pattern = String::deserialize(deserializer)?;
+    Regex::new(&pattern).map_err(de::Error::custom)
+}
+
+fn serialize_regex<S: Serializer>(regex: &Regex, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(regex.as_str())
+}
@@ -1,8 +1,105 @@
-use serde::Deserialize;
+use std::{collections::BTreeMap, fmt, marker::PhantomData};
 
+use serde::{
+    de::{self, value::MapAccessDeserializer, value::SeqAccessDeserializer, MapAccess, SeqAccess},
+    Deserialize, Deserializer, Serialize,
+};
+
+use crate::utils::defaults;
+
+pub mod basic;
+pub mod client_cert;
 pub mod saml;
 
-#[derive(Debug, Deserialize)]
+/// Each module can be configured as a single table (`[modules.saml]`) or, to run several
+/// instances side by side, as an array of tables (`[[modules.saml]]`)
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Settings {
-    pub saml: saml::Settings,
+    #[serde(default = "defaults::modules::basic", deserialize_with = "one_or_many")]
+    pub basic: Vec<basic::Settings>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub saml: Vec<saml::Settings>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub client_cert: Vec<client_cert::Settings>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            basic: defaults::modules::basic(),
+            saml: vec![],
+            client_cert: vec![],
+        }
+    }
+}
+
+/// Options shared by all login modules
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Common {
+    #[serde(default = "defaults::modules::enabled")]
+    pub enabled: bool,
+    /// Unique identifier of the module instance, defaults to the module's own
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Modules are listed on the login page in ascending order
+    #[serde(default)]
+    pub order: i32,
+    /// Label of the module on the login page, defaults to the module's own
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// Translations of the label by language code, e.g. `{ nl = "Externe gebruikers" }`
+    #[serde(default)]
+    pub display_names: BTreeMap<String, String>,
+    /// Path of the module under `/login`, e.g. `/saml`. Defaults to the module's own.
+    #[serde(default)]
+    pub subpath: Option<String>,
+    /// Groups of users logged in through this module, passed on in issued JWTs
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+impl Default for Common {
+    fn default() -> Self {
+        Self {
+            enabled: defaults::modules::enabled(),
+            name: None,
+            order: 0,
+            display_name: None,
+            display_names: BTreeMap::new(),
+            subpath: None,
+            groups: vec![],
+        }
+    }
+}
+
+impl Common {
+    pub fn name_or(&self, default: &str) -> String {
+        self.name.clone().unwrap_or_else(|| default.to_owned())
+    }
+}
+
+fn one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    struct OneOrMany<T>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> de::Visitor<'de> for OneOrMany<T> {
+        type Value = Vec<T>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a table or an array of tables")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+            T::deserialize(MapAccessDeserializer::new(map)).map(|one| vec![one])
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+            Vec::deserialize(SeqAccessDeserializer::new(seq))
+        }
+    }
+
+    deserializer.deserialize_any(OneOrMany(PhantomData))
 }
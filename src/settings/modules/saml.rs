@@ -1,7 +1,23 @@
-use serde::Deserialize;
+use color_eyre::{eyre::bail, Result};
+use serde::{Deserialize, Serialize};
 use url::Url;
 
-#[derive(Debug, Deserialize, Clone)]
+use super::Common;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Settings {
+    #[serde(flatten)]
+    pub common: Common,
     pub upstream_url: Url,
 }
+
+impl Settings {
+    pub fn sanity_check(&self) -> Result<()> {
+        let url = &self.upstream_url;
+        if !matches!(url.scheme(), "http" | "https") || !url.has_host() {
+            bail!("SAML upstream_url '{url}' must be an http(s) URL");
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+use crate::utils::defaults;
+
+/// Reloading the configuration file, which always happens on SIGHUP
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Settings {
+    /// Also reload when the file changes
+    #[serde(default)]
+    pub watch: bool,
+    /// How often the file is checked for changes, in seconds
+    #[serde(default = "defaults::reload::interval_secs")]
+    pub interval_secs: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            watch: false,
+            interval_secs: defaults::reload::interval_secs(),
+        }
+    }
+}
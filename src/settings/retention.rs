@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+use crate::utils::defaults;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Settings {
+    #[serde(default)]
+    pub login_entries: Policy,
+    #[serde(default)]
+    pub access_entries: Policy,
+    /// How often the background task prunes entries, in seconds
+    #[serde(default = "defaults::retention::interval_secs")]
+    pub interval_secs: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            login_entries: Policy::default(),
+            access_entries: Policy::default(),
+            interval_secs: defaults::retention::interval_secs(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn is_enabled(&self) -> bool {
+        self.login_entries.is_enabled() || self.access_entries.is_enabled()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct Policy {
+    /// Entries older than this many days are deleted
+    #[serde(default)]
+    pub max_age_days: Option<u32>,
+    /// Only the newest this many entries are kept
+    #[serde(default)]
+    pub max_rows: Option<u64>,
+}
+
+impl Policy {
+    pub fn is_enabled(&self) -> bool {
+        self.max_age_days.is_some() || self.max_rows.is_some()
+    }
+}
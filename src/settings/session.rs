@@ -0,0 +1,121 @@
+use actix_web::cookie::SameSite as CookieSameSite;
+use color_eyre::{eyre::bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::defaults;
+
+/// Prefix that makes browsers reject the cookie unless it is `Secure`, has path `/` and no domain
+const HOST_PREFIX: &str = "__Host-";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SameSite {
+    Strict,
+    #[default]
+    Lax,
+    /// Also sent on cross-site requests; requires `secure`
+    None,
+}
+
+impl From<SameSite> for CookieSameSite {
+    fn from(same_site: SameSite) -> Self {
+        match same_site {
+            SameSite::Strict => Self::Strict,
+            SameSite::Lax => Self::Lax,
+            SameSite::None => Self::None,
+        }
+    }
+}
+
+/// The session cookie, which holds the whole session
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Settings {
+    #[serde(default = "defaults::session::cookie_name")]
+    pub cookie_name: String,
+    /// Prefix the cookie name with `__Host-`, so it can't be set by other subdomains
+    #[serde(default)]
+    pub host_prefix: bool,
+    /// Domain the cookie is sent to, including its subdomains. Defaults to the exact host.
+    #[serde(default)]
+    pub domain: Option<String>,
+    /// Path the cookie is sent to, defaults to `http.path`
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Only send the cookie over HTTPS
+    #[serde(default = "defaults::session::secure")]
+    pub secure: bool,
+    #[serde(default)]
+    pub same_site: SameSite,
+    /// Keep the session when the browser is closed, instead of only for the browser session
+    #[serde(default)]
+    pub persistent: bool,
+    /// How long a session lasts without activity, in seconds
+    #[serde(default = "defaults::session::ttl_secs")]
+    pub ttl_secs: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            cookie_name: defaults::session::cookie_name(),
+            host_prefix: false,
+            domain: None,
+            path: None,
+            secure: defaults::session::secure(),
+            same_site: SameSite::default(),
+            persistent: false,
+            ttl_secs: defaults::session::ttl_secs(),
+        }
+    }
+}
+
+impl Settings {
+    /// Name of the cookie, including the `__Host-` prefix if enabled
+    pub fn cookie_name(&self) -> String {
+        if self.host_prefix {
+            format!("{HOST_PREFIX}{}", self.cookie_name)
+        } else {
+            self.cookie_name.clone()
+        }
+    }
+
+    /// Checks the cookie on its own, `cookie_path` and the listener setup are checked along with
+    /// the HTTP settings
+    pub fn sanity_check(&self) -> Result<()> {
+        let name = &self.cookie_name;
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c))
+        {
+            bail!("Session cookie_name '{name}' is not a valid cookie name");
+        }
+        if name.starts_with("__Host-") || name.starts_with("__Secure-") {
+            bail!("Session cookie_name '{name}' must not contain a prefix, use `host_prefix`");
+        }
+
+        if let Some(domain) = &self.domain {
+            if domain.is_empty() || domain.contains(['/', ':', ' ']) {
+                bail!("Session cookie domain '{domain}' must be a host name");
+            }
+        }
+        if let Some(path) = &self.path {
+            if !path.starts_with('/') {
+                bail!("Session cookie path '{path}' must start with '/'");
+            }
+        }
+
+        if self.same_site == SameSite::None && !self.secure {
+            bail!("Session cookies with same_site = \"none\" must be secure");
+        }
+        if self.host_prefix && (!self.secure || self.domain.is_some()) {
+            bail!("Session cookies with host_prefix must be secure and can't have a domain");
+        }
+        // Browsers cap cookie lifetimes at 400 days
+        if self.ttl_secs == 0 || self.ttl_secs > 400 * 24 * 60 * 60 {
+            bail!("Session ttl_secs must be greater than zero and at most 400 days");
+        }
+
+        Ok(())
+    }
+}
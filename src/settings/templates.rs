@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::defaults;
+
+/// Branding of the pages mordor serves
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Settings {
+    /// Directory with templates that replace the built-in ones of the same name: `base.html`,
+    /// `login.html`, `logout.html`, `error.html` and `tokens.html`. Files in its `static`
+    /// subdirectory are served under `/static/`, translations in its `i18n` subdirectory
+    /// (`<language>.json`) extend or replace the built-in ones.
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    /// Read the templates again for every page, so changes show up without a reload. Meant for
+    /// development.
+    #[serde(default)]
+    pub live_reload: bool,
+    /// Language of pages for browsers that don't accept any of the available ones
+    #[serde(default = "defaults::templates::default_language")]
+    pub default_language: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            live_reload: false,
+            default_language: defaults::templates::default_language(),
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use std::{net::SocketAddr, path::PathBuf};
+
+use color_eyre::{eyre::bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::defaults;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientAuth {
+    /// Client certificates are not requested
+    #[default]
+    None,
+    /// Client certificates are requested and verified if presented
+    Optional,
+    /// Connections without a valid client certificate are rejected
+    Required,
+}
+
+/// TLS termination for all TCP listeners
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Settings {
+    /// PEM file with the certificate chain, leaf certificate first
+    pub cert_file: PathBuf,
+    /// PEM file with the private key (PKCS#8, PKCS#1 or SEC1)
+    pub key_file: PathBuf,
+    /// How often the certificate and key files are checked for changes, in seconds
+    #[serde(default = "defaults::tls::reload_interval_secs")]
+    pub reload_interval_secs: u64,
+    #[serde(default)]
+    pub client_auth: ClientAuth,
+    /// PEM bundle of CAs trusted to issue client certificates
+    #[serde(default)]
+    pub client_ca_file: Option<PathBuf>,
+    /// Plain HTTP addresses that redirect all requests to HTTPS
+    #[serde(default)]
+    pub redirect_listen: Vec<SocketAddr>,
+    /// Port used in redirects to HTTPS, defaults to the port of the first TCP listener
+    #[serde(default)]
+    pub https_port: Option<u16>,
+}
+
+impl Settings {
+    pub fn sanity_check(&self) -> Result<()> {
+        if self.client_auth != ClientAuth::None && self.client_ca_file.is_none() {
+            bail!("TLS client authentication requires `client_ca_file`");
+        }
+        if self.reload_interval_secs == 0 {
+            bail!("TLS reload interval must be greater than zero");
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use actix_session::Session;
+use actix_web::{
+    http::header::{AcceptLanguage, Header, Preference},
+    HttpRequest,
+};
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use tera::{Context, Tera};
+use tracing::{error, warn};
+use url::form_urlencoded;
+
+use crate::settings::templates::Settings;
+
+/// Session key of the language chosen with the `lang` query parameter
+const LANGUAGE_SESSION_KEY: &str = "lang";
+
+/// Language of the built-in texts, used for anything a catalog doesn't translate
+const FALLBACK_LANGUAGE: &str = "en";
+
+/// Texts of the pages in one language, by key
+type Catalog = BTreeMap<String, String>;
+
+lazy_static! {
+    static ref BUILTIN: Tera = {
+        let mut tera = Tera::default();
+        match tera.add_raw_templates(vec![
+            (
+                "base.html",
+                include_str!(concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/src/_static/base.html"
+                )),
+            ),
+            (
+                "login.html",
+                include_str!(concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/src/_static/login.html"
+                )),
+            ),
+            (
+                "logout.html",
+                include_str!(concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/src/_static/logout.html"
+                )),
+            ),
+            (
+                "error.html",
+                include_str!(concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/src/_static/error.html"
+                )),
+            ),
+            (
+                "tokens.html",
+                include_str!(concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/src/_static/tokens.html"
+                )),
+            ),
+        ]) {
+            Ok(_) => tera,
+            Err(e) => {
+                error!("Error parsing templates: {e:?}");
+                panic!("Error parsing templates: {e:?}");
+            }
+        }
+    };
+}
+
+lazy_static! {
+    static ref BUILTIN_CATALOGS: BTreeMap<String, Catalog> = [
+        (
+            "en",
+            include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/src/_static/i18n/en.json"
+            ))
+        ),
+        (
+            "nl",
+            include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/src/_static/i18n/nl.json"
+            ))
+        ),
+    ]
+    .into_iter()
+    .map(|(language, catalog)| match serde_json::from_str(catalog) {
+        Ok(catalog) => (language.to_owned(), catalog),
+        Err(e) => {
+            error!("Error parsing {language} translations: {e:?}");
+            panic!("Error parsing {language} translations: {e:?}");
+        }
+    })
+    .collect();
+}
+
+/// Entry of the language switcher
+#[derive(Debug, Serialize)]
+struct LanguageLink {
+    code: String,
+    name: String,
+    url: String,
+    current: bool,
+}
+
+/// The built-in templates and translations, with those in the configured directory taking their
+/// place
+pub struct Templates {
+    tera: Tera,
+    catalogs: BTreeMap<String, Catalog>,
+    settings: Settings,
+}
+
+impl Templates {
+    pub fn load(settings: &Settings) -> Result<Self> {
+        let catalogs = load_catalogs(settings)?;
+        if !catalogs.contains_key(&settings.default_language) {
+            bail!(
+                "There are no translations for default language '{}'",
+                settings.default_language
+            );
+        }
+
+        Ok(Self {
+            tera: build(settings)?,
+            catalogs,
+            settings: settings.clone(),
+        })
+    }
+
+    /// Picks the language of a page: the one chosen with `?lang=`, which is remembered in the
+    /// session, or else the one the browser prefers most out of those available
+    pub fn language(&self, req: &HttpRequest, session: &Session) -> String {
+        let requested = form_urlencoded::parse(req.query_string().as_bytes())
+            .find(|(key, _)| key == "lang")
+            .map(|(_, language)| language.to_lowercase());
+        if let Some(language) = requested.filter(|language| self.catalogs.contains_key(language)) {
+            if let Err(e) = session.insert(LANGUAGE_SESSION_KEY, &language) {
+                warn!("Could not remember language '{language}': {e}");
+            }
+            return language;
+        }
+
+        if let Ok(Some(language)) = session.get::<String>(LANGUAGE_SESSION_KEY) {
+            if self.catalogs.contains_key(&language) {
+                return language;
+            }
+        }
+
+        if let Ok(accept_language) = AcceptLanguage::parse(req) {
+            for preference in accept_language.ranked() {
+                let Preference::Specific(tag) = preference else {
+                    break;
+                };
+                let language = tag.primary_language().to_lowercase();
+                if self.catalogs.contains_key(&language) {
+                    return language;
+                }
+            }
+        }
+
+        self.settings.default_language.clone()
+    }
+
+    /// Context with the texts in the given language as `t`, and the language switcher as
+    /// `languages`
+    pub fn context(&self, req: &HttpRequest, language: &str) -> Context {
+        let mut context = Context::new();
+        context.insert("lang", language);
+        context.insert("t", &self.catalog(language));
+
+        if self.catalogs.len() > 1 {
+            let links: Vec<LanguageLink> = self
+                .catalogs
+                .keys()
+                .map(|code| LanguageLink {
+                    code: code.clone(),
+                    name: self.translate(code, "language_name"),
+                    url: language_url(req, code),
+                    current: code == language,
+                })
+                .collect();
+            context.insert("languages", &links);
+        }
+
+        context
+    }
+
+    /// Text by its key, in the given language if it has been translated
+    pub fn translate(&self, language: &str, key: &str) -> String {
+        [language, &self.settings.default_language, FALLBACK_LANGUAGE]
+            .iter()
+            .find_map(|language| self.catalogs.get(*language)?.get(key))
+            .cloned()
+            .unwrap_or_else(|| key.to_owned())
+    }
+
+    /// All texts in the given language, falling back to the default language for missing ones
+    fn catalog(&self, language: &str) -> Catalog {
+        let mut catalog = Catalog::new();
+        for language in [FALLBACK_LANGUAGE, &self.settings.default_language, language] {
+            if let Some(texts) = self.catalogs.get(language) {
+                catalog.extend(texts.clone());
+            }
+        }
+        catalog
+    }
+
+    pub fn render(&self, name: &str, context: &Context) -> Result<String, tera::Error> {
+        if self.settings.live_reload {
+            match build(&self.settings) {
+                Ok(tera) => return tera.render(name, context),
+                Err(e) => error!("Error reloading templates, using the previous ones: {e:#}"),
+            }
+        }
+
+        self.tera.render(name, context)
+    }
+
+    /// Directory with logos, stylesheets and the like, served under `/static/`
+    pub fn static_dir(&self) -> Option<PathBuf> {
+        self.settings
+            .dir
+            .as_ref()
+            .map(|dir| dir.join("static"))
+            .filter(|dir| dir.is_dir())
+    }
+}
+
+/// The current URL with another language selected
+fn language_url(req: &HttpRequest, language: &str) -> String {
+    let mut query = form_urlencoded::Serializer::new(String::new());
+    for (key, value) in form_urlencoded::parse(req.query_string().as_bytes()) {
+        if key != "lang" {
+            query.append_pair(&key, &value);
+        }
+    }
+    query.append_pair("lang", language);
+    format!("{}?{}", req.path(), query.finish())
+}
+
+/// Built-in catalogs, extended by `<language>.json` files in the `i18n` subdirectory
+fn load_catalogs(settings: &Settings) -> Result<BTreeMap<String, Catalog>> {
+    let mut catalogs = BUILTIN_CATALOGS.clone();
+    let Some(dir) = settings
+        .dir
+        .as_ref()
+        .map(|dir| dir.join("i18n"))
+        .filter(|dir| dir.is_dir())
+    else {
+        return Ok(catalogs);
+    };
+
+    for entry in fs::read_dir(&dir).wrap_err_with(|| format!("Could not read {}", dir.display()))? {
+        let path = entry?.path();
+        let Some(language) = path
+            .file_stem()
+            .filter(|_| {
+                path.extension()
+                    .is_some_and(|extension| extension == "json")
+            })
+            .map(|language| language.to_string_lossy().to_lowercase())
+        else {
+            continue;
+        };
+
+        let contents = fs::read_to_string(&path)
+            .wrap_err_with(|| format!("Could not read {}", path.display()))?;
+        let catalog: Catalog = serde_json::from_str(&contents)
+            .wrap_err_with(|| format!("Could not parse translations in {}", path.display()))?;
+        catalogs.entry(language).or_default().extend(catalog);
+    }
+
+    Ok(catalogs)
+}
+
+fn build(settings: &Settings) -> Result<Tera> {
+    let mut tera = BUILTIN.clone();
+    let Some(dir) = &settings.dir else {
+        return Ok(tera);
+    };
+
+    let mut files = vec![];
+    for entry in fs::read_dir(dir).wrap_err_with(|| format!("Could not read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_file()
+            && path
+                .extension()
+                .is_some_and(|extension| extension == "html")
+        {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned());
+            files.push((path, name));
+        }
+    }
+    tera.add_template_files(files)
+        .wrap_err_with(|| format!("Could not load templates from {}", dir.display()))?;
+
+    Ok(tera)
+}
@@ -0,0 +1,184 @@
+use std::{
+    any::Any,
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use actix_tls::accept::rustls_0_21::TlsStream;
+use actix_web::{dev::Extensions, rt::net::TcpStream};
+use color_eyre::{
+    eyre::{bail, eyre, WrapErr},
+    Result,
+};
+use rustls::{
+    server::{
+        AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient, ClientHello,
+        NoClientAuth, ResolvesServerCert,
+    },
+    sign::{self, CertifiedKey},
+    Certificate, PrivateKey, RootCertStore, ServerConfig,
+};
+use rustls_pemfile::Item;
+use tracing::{debug, error, info};
+
+use crate::settings::tls::{ClientAuth, Settings};
+
+/// Serves the certificate from `cert_file`/`key_file`, picking up changes to the files whenever
+/// `reload_if_changed` is called
+pub struct CertificateResolver {
+    cert_file: PathBuf,
+    key_file: PathBuf,
+    current: RwLock<Arc<CertifiedKey>>,
+    modified: RwLock<(SystemTime, SystemTime)>,
+}
+
+impl CertificateResolver {
+    pub fn load(settings: &Settings) -> Result<Self> {
+        let modified = modification_times(&settings.cert_file, &settings.key_file)?;
+        let certified_key = load_certified_key(&settings.cert_file, &settings.key_file)?;
+
+        Ok(Self {
+            cert_file: settings.cert_file.clone(),
+            key_file: settings.key_file.clone(),
+            current: RwLock::new(Arc::new(certified_key)),
+            modified: RwLock::new(modified),
+        })
+    }
+
+    /// Reloads the certificate if either file was modified since it was last loaded. On failure,
+    /// the previous certificate stays in use.
+    pub fn reload_if_changed(&self) -> Result<bool> {
+        let modified = modification_times(&self.cert_file, &self.key_file)?;
+        if *self.modified.read().expect("lock poisoned") == modified {
+            return Ok(false);
+        }
+
+        let certified_key = load_certified_key(&self.cert_file, &self.key_file)?;
+        *self.current.write().expect("lock poisoned") = Arc::new(certified_key);
+        *self.modified.write().expect("lock poisoned") = modified;
+        Ok(true)
+    }
+}
+
+impl ResolvesServerCert for CertificateResolver {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().expect("lock poisoned").clone())
+    }
+}
+
+/// Builds the rustls configuration shared by all TLS listeners
+pub fn server_config(
+    settings: &Settings,
+    resolver: Arc<CertificateResolver>,
+) -> Result<ServerConfig> {
+    let builder = ServerConfig::builder().with_safe_defaults();
+
+    let builder = match (settings.client_auth, &settings.client_ca_file) {
+        (ClientAuth::None, _) => builder.with_client_cert_verifier(NoClientAuth::boxed()),
+        (ClientAuth::Optional, Some(ca_file)) => builder.with_client_cert_verifier(
+            AllowAnyAnonymousOrAuthenticatedClient::new(load_roots(ca_file)?).boxed(),
+        ),
+        (ClientAuth::Required, Some(ca_file)) => builder.with_client_cert_verifier(
+            AllowAnyAuthenticatedClient::new(load_roots(ca_file)?).boxed(),
+        ),
+        (_, None) => bail!("TLS client authentication requires `client_ca_file`"),
+    };
+
+    Ok(builder.with_cert_resolver(resolver))
+}
+
+/// DER encoded leaf certificate the client presented during the TLS handshake. Only available
+/// when `client_auth` is enabled, in which case it has been verified against `client_ca_file`.
+#[derive(Debug, Clone)]
+pub struct PeerCertificate(pub Vec<u8>);
+
+/// Connection hook that makes the client certificate available to handlers through
+/// `HttpRequest::conn_data`
+pub fn on_connect(connection: &dyn Any, data: &mut Extensions) {
+    if let Some(stream) = connection.downcast_ref::<TlsStream<TcpStream>>() {
+        let (_, session) = stream.get_ref();
+        if let Some(certificate) = session.peer_certificates().and_then(|certs| certs.first()) {
+            data.insert(PeerCertificate(certificate.0.clone()));
+        }
+    }
+}
+
+/// Periodically checks the certificate files for changes, for as long as the application runs
+pub async fn watch(resolver: Arc<CertificateResolver>, interval_secs: u64) {
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+    // The first tick completes immediately, and the certificate was just loaded
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        match resolver.reload_if_changed() {
+            Ok(true) => info!(
+                "Reloaded TLS certificate from {}",
+                resolver.cert_file.display()
+            ),
+            Ok(false) => debug!("TLS certificate unchanged"),
+            Err(e) => error!("Error reloading TLS certificate, keeping the current one: {e:#}"),
+        }
+    }
+}
+
+fn modification_times(cert_file: &Path, key_file: &Path) -> Result<(SystemTime, SystemTime)> {
+    let modified = |path: &Path| {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .wrap_err_with(|| format!("Could not read {}", path.display()))
+    };
+
+    Ok((modified(cert_file)?, modified(key_file)?))
+}
+
+fn read_pem(path: &Path) -> Result<Vec<Item>> {
+    let file = File::open(path).wrap_err_with(|| format!("Could not open {}", path.display()))?;
+    rustls_pemfile::read_all(&mut BufReader::new(file))
+        .wrap_err_with(|| format!("Could not parse {}", path.display()))
+}
+
+fn load_certified_key(cert_file: &Path, key_file: &Path) -> Result<CertifiedKey> {
+    let certificates: Vec<Certificate> = read_pem(cert_file)?
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::X509Certificate(der) => Some(Certificate(der)),
+            _ => None,
+        })
+        .collect();
+    if certificates.is_empty() {
+        bail!("No certificates found in {}", cert_file.display());
+    }
+
+    let key = read_pem(key_file)?
+        .into_iter()
+        .find_map(|item| match item {
+            Item::PKCS8Key(der) | Item::RSAKey(der) | Item::ECKey(der) => Some(PrivateKey(der)),
+            _ => None,
+        })
+        .ok_or_else(|| eyre!("No private key found in {}", key_file.display()))?;
+    let signing_key = sign::any_supported_type(&key)
+        .map_err(|e| eyre!("Unsupported private key in {}: {e}", key_file.display()))?;
+
+    Ok(CertifiedKey::new(certificates, signing_key))
+}
+
+fn load_roots(ca_file: &Path) -> Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for item in read_pem(ca_file)? {
+        if let Item::X509Certificate(der) = item {
+            roots
+                .add(&Certificate(der))
+                .wrap_err_with(|| format!("Invalid CA certificate in {}", ca_file.display()))?;
+        }
+    }
+    if roots.is_empty() {
+        bail!("No CA certificates found in {}", ca_file.display());
+    }
+
+    Ok(roots)
+}
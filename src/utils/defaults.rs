@@ -1,8 +1,8 @@
 pub mod http {
-    use std::net::{Ipv4Addr, SocketAddrV4};
+    use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
 
-    pub fn address() -> SocketAddrV4 {
-        SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 8080)
+    pub fn address() -> SocketAddr {
+        SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 8080))
     }
 }
 
@@ -10,6 +10,129 @@ pub mod database {
     pub fn file() -> String {
         "mordor.db".to_string()
     }
+
+    pub const fn migrate() -> bool {
+        true
+    }
+}
+
+pub mod access_entries {
+    pub const fn batch_size() -> usize {
+        100
+    }
+
+    pub const fn flush_interval_ms() -> u64 {
+        1000
+    }
+
+    pub const fn queue_size() -> usize {
+        10_000
+    }
+
+    pub const fn record_field() -> bool {
+        true
+    }
+}
+
+pub mod api_tokens {
+    pub const fn self_service() -> bool {
+        true
+    }
+
+    pub const fn default_lifetime_days() -> u32 {
+        90
+    }
+}
+
+pub mod jwt {
+    use crate::settings::jwt::Claim;
+
+    pub fn header() -> String {
+        "X-Mordor-JWT".to_owned()
+    }
+
+    pub const fn ttl_secs() -> u64 {
+        300
+    }
+
+    pub fn claims() -> Vec<Claim> {
+        vec![Claim::Groups, Claim::Module]
+    }
+}
+
+pub mod modules {
+    use regex::Regex;
+
+    use crate::settings::modules::{basic, client_cert};
+
+    pub const fn enabled() -> bool {
+        true
+    }
+
+    pub fn basic() -> Vec<basic::Settings> {
+        vec![basic::Settings::default()]
+    }
+
+    /// Use the subject's common name as the username
+    pub fn client_cert_rules() -> Vec<client_cert::Rule> {
+        vec![client_cert::Rule {
+            field: client_cert::CertificateField::CommonName,
+            pattern: Regex::new("^(.+)$").expect("valid regex"),
+            username: client_cert_username(),
+        }]
+    }
+
+    pub fn client_cert_username() -> String {
+        "$1".to_owned()
+    }
+}
+
+pub mod session {
+    pub fn cookie_name() -> String {
+        "mordor-session".to_owned()
+    }
+
+    pub const fn secure() -> bool {
+        true
+    }
+
+    pub const fn ttl_secs() -> u64 {
+        24 * 60 * 60
+    }
+}
+
+pub mod logging {
+    pub fn level() -> String {
+        "info".to_owned()
+    }
+
+    pub fn file_name() -> String {
+        "mordor.log".to_owned()
+    }
+}
+
+pub mod templates {
+    pub fn default_language() -> String {
+        "en".to_owned()
+    }
+}
+
+pub mod tls {
+    pub const fn reload_interval_secs() -> u64 {
+        60
+    }
+}
+
+pub mod reload {
+    pub const fn interval_secs() -> u64 {
+        5
+    }
+}
+
+pub mod retention {
+    pub const fn interval_secs() -> u64 {
+        60 * 60
+    }
 }
 
 pub const fn store_access_entries() -> bool {
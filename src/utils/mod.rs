@@ -1,7 +1,15 @@
-use reqwest::{redirect::Policy, Client};
+use reqwest::{
+    header::{HeaderMap, HeaderValue},
+    redirect::Policy,
+    Client,
+};
+use time::{macros::format_description, OffsetDateTime, UtcOffset};
 use tracing::error;
 
-use crate::errors::AppError;
+use crate::{
+    controllers::request_id::{RequestId, REQUEST_ID_HEADER},
+    errors::AppError,
+};
 
 pub mod defaults;
 
@@ -15,12 +23,29 @@ macro_rules! enclose {
     };
 }
 
-pub fn get_http_client() -> Result<Client, AppError> {
+/// Client for upstream requests made on behalf of a request, passing on its ID
+pub fn get_http_client(request_id: &RequestId) -> Result<Client, AppError> {
+    let mut headers = HeaderMap::new();
+    if let Ok(value) = HeaderValue::from_str(request_id.as_str()) {
+        headers.insert(REQUEST_ID_HEADER, value);
+    }
+
     Client::builder()
         .redirect(Policy::none())
+        .default_headers(headers)
         .build()
         .map_err(|err| {
             error!("Error building HTTP client: {:?}", err);
             AppError::Internal
         })
 }
+
+/// Formats a timestamp for humans, e.g. `2024-03-01 12:00 UTC`
+pub fn format_timestamp(timestamp: OffsetDateTime) -> String {
+    timestamp
+        .to_offset(UtcOffset::UTC)
+        .format(format_description!(
+            "[year]-[month]-[day] [hour]:[minute] UTC"
+        ))
+        .unwrap_or_else(|_| timestamp.to_string())
+}